@@ -0,0 +1,29 @@
+//! The service used by the tests of the runtime crates.
+//!
+//! Each runtime crate includes this file as a module of its tests that need a service to serve.
+
+use serde::Deserialize;
+use zlink::varlink_service::Info;
+
+// A service with a single method that does nothing.
+#[derive(Debug, Clone)]
+pub(crate) struct Ping;
+
+#[zlink::service(interface = "org.example.ping")]
+impl Ping {
+    async fn ping(&mut self) {}
+}
+
+// The information about the service to serve it with.
+pub(crate) fn info() -> Info<'static> {
+    Info {
+        product: "ping",
+        ..Info::default()
+    }
+}
+
+// The part of the reply to `org.varlink.service.GetInfo` we check.
+#[derive(Debug, Deserialize)]
+pub(crate) struct InfoReply<'a> {
+    pub(crate) product: &'a str,
+}
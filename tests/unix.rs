@@ -0,0 +1,178 @@
+//! Test suite shared by the Unix Domain Socket transports of the runtime crates.
+//!
+//! Each runtime crate includes this file as a module of one of its integration tests. The parent
+//! module must provide `rt`, the runtime crate, `block_on`, which runs a future to completion on
+//! the runtime, and `ping`, the `ping.rs` service next to this file.
+
+use std::{
+    fs::{self, Permissions},
+    io::{Read, Write},
+    os::{
+        fd::AsFd,
+        unix::{fs::PermissionsExt, net::UnixStream},
+    },
+    path::PathBuf,
+};
+
+use futures_util::{
+    future::{join, select, Either},
+    pin_mut,
+};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use zlink::{connection::FdIndex, server::Listener as _, varlink_service, Server};
+
+use super::{
+    block_on,
+    ping::{self, InfoReply, Ping},
+    rt::unix,
+};
+
+#[test]
+fn bind() {
+    block_on(async {
+        let path = socket_path("bind");
+
+        // A stale socket file.
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let mut listener =
+            unix::bind_with_permissions(&path, Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(listener.path(), Some(path.as_path()));
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        // Can't bind while someone is listening.
+        assert!(unix::bind(&path).is_err());
+
+        let (client, server) = join(unix::connect(&path), listener.accept::<&'static str>()).await;
+        client.unwrap();
+        server.unwrap();
+
+        drop(listener);
+        assert!(!path.exists());
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn bind_abstract() {
+    block_on(async {
+        let name = format!("zlink-suite-{}", std::process::id());
+        let mut listener = unix::bind_abstract(&name).unwrap();
+        assert!(listener.path().is_none());
+
+        // Can't bind twice.
+        assert!(unix::bind_abstract(&name).is_err());
+
+        let (client, server) = join(
+            unix::connect_abstract(&name),
+            listener.accept::<&'static str>(),
+        )
+        .await;
+        client.unwrap();
+        server.unwrap();
+    })
+}
+
+#[test]
+fn serve() {
+    block_on(async {
+        let path = socket_path("serve");
+        let listener = unix::bind(&path).unwrap();
+        let mut server = Server::with_info(listener, ping::info());
+        let server = server.run::<_, &'static str>(Ping);
+
+        let client = async {
+            let mut connection = unix::connect(&path).await.unwrap();
+            connection
+                .call_method::<_, IgnoredAny, &'static str>(Method::Ping)
+                .await
+                .unwrap();
+            let reply = connection
+                .call_method::<_, InfoReply<'_>, varlink_service::Error>(
+                    varlink_service::Method::GetInfo,
+                )
+                .await
+                .unwrap();
+            assert_eq!(reply.parameters().unwrap().product, "ping");
+        };
+
+        pin_mut!(server, client);
+        if let Either::Left((res, _)) = select(server, client).await {
+            panic!("server exited: {res:?}");
+        }
+    })
+}
+
+#[test]
+fn fds() {
+    block_on(async {
+        let path = socket_path("fds");
+        let mut listener = unix::bind(&path).unwrap();
+        let (client, server) = join(unix::connect(&path), listener.accept::<&'static str>()).await;
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+
+        let (mut ours, theirs) = UnixStream::pair().unwrap();
+        let call = Method::Write {
+            fd: FdIndex::new(0),
+        };
+        client
+            .send_call_with_fds::<_, &'static str>(call, None, None, None, &[theirs.as_fd()])
+            .await
+            .unwrap();
+        drop(theirs);
+
+        let (call, fds) = server
+            .receive_call_with_fds::<Method, &'static str>()
+            .await
+            .unwrap();
+        let Method::Write { fd } = call.into_method() else {
+            panic!("unexpected method call");
+        };
+        let mut stream = UnixStream::from(fds.into_iter().nth(fd.get()).unwrap());
+        stream.write_all(b"hello").unwrap();
+        drop(stream);
+
+        let mut received = String::new();
+        ours.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "hello");
+    })
+}
+
+#[test]
+fn peer_credentials() {
+    use std::os::unix::fs::MetadataExt;
+
+    block_on(async {
+        let path = socket_path("credentials");
+        let mut listener = unix::bind(&path).unwrap();
+        let (client, server) = join(unix::connect(&path), listener.accept::<&'static str>()).await;
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+
+        // We created the socket file so its owner is our user.
+        let metadata = fs::metadata(&path).unwrap();
+        for connection in [&mut client, &mut server] {
+            let credentials = connection.peer_credentials::<&'static str>().unwrap();
+            assert_eq!(credentials.uid(), metadata.uid());
+            assert_eq!(credentials.gid(), metadata.gid());
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            assert_eq!(credentials.pid(), Some(std::process::id() as i32));
+        }
+    })
+}
+
+fn socket_path(test: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("zlink-suite-{test}-{}.sock", std::process::id()))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", content = "parameters")]
+enum Method {
+    #[serde(rename = "org.example.ping.Ping")]
+    Ping,
+    #[serde(rename = "org.example.ping.Write")]
+    Write { fd: FdIndex },
+}
@@ -0,0 +1,3 @@
+fn main() {
+    zlink_codegen::compile(&["org.example.ftl.varlink"]).unwrap();
+}
@@ -0,0 +1,281 @@
+//! Tests for the code generated by `zlink-codegen`.
+//!
+//! The build script generates the code for `org.example.ftl.varlink`, which is included here so
+//! that it's compiled and exercised like in any crate using `zlink-codegen`.
+
+include!(concat!(env!("OUT_DIR"), "/org_example_ftl.rs"));
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_util::future::join;
+    use zlink::{connection::Channel, server::serve_next, varlink_service::Info, Connection};
+
+    use super::*;
+
+    #[test]
+    fn method_call() {
+        let call = FtlMethodCall::CalculateConfiguration {
+            current: Coordinate {
+                longitude: 1.0,
+                latitude: 2.0,
+                distance: 3,
+            },
+            target: Coordinate {
+                longitude: 4.0,
+                latitude: 5.0,
+                distance: 6,
+            },
+        };
+        let json = serde_json::to_string(&call).unwrap();
+        assert_eq!(serde_json::from_str::<FtlMethodCall>(&json).unwrap(), call);
+
+        let call = FtlMethodCall::Jump { config: None };
+        let json = serde_json::to_string(&call).unwrap();
+        assert_eq!(json, r#"{"method":"org.example.ftl.Jump","parameters":{}}"#);
+        assert_eq!(serde_json::from_str::<FtlMethodCall>(&json).unwrap(), call);
+
+        for json in [
+            r#"{"method":"org.example.ftl.Monitor"}"#,
+            r#"{"method":"org.example.ftl.Monitor","parameters":{}}"#,
+        ] {
+            assert_eq!(
+                serde_json::from_str::<FtlMethodCall>(json).unwrap(),
+                FtlMethodCall::Monitor
+            );
+        }
+    }
+
+    #[test]
+    fn error() {
+        let error = FtlError::ParameterOutOfRange {
+            field: "distance".into(),
+        };
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            json,
+            r#"{"error":"org.example.ftl.ParameterOutOfRange","parameters":{"field":"distance"}}"#
+        );
+        assert_eq!(serde_json::from_str::<FtlError>(&json).unwrap(), error);
+        assert_eq!(
+            error.to_string(),
+            r#"Parameter out of range (field: "distance")"#
+        );
+
+        for json in [
+            r#"{"error":"org.example.ftl.NotEnoughEnergy"}"#,
+            r#"{"error":"org.example.ftl.NotEnoughEnergy","parameters":{}}"#,
+        ] {
+            assert_eq!(
+                serde_json::from_str::<FtlError>(json).unwrap(),
+                FtlError::NotEnoughEnergy
+            );
+        }
+        assert_eq!(FtlError::NotEnoughEnergy.to_string(), "Not enough energy");
+    }
+
+    #[test]
+    fn proxy_and_service() {
+        let channel = Channel::new();
+        let (client, server) = channel.split();
+        let mut proxy = FtlProxy::new(Connection::new(client));
+        let mut server = Connection::new(server);
+        let mut handler = FtlHandler(Drive {
+            condition: DriveCondition {
+                state: DriveConditionState::Idle,
+                tylium_level: 100,
+            },
+            caller: None,
+        });
+        let info = Info {
+            descriptions: &[DESCRIPTION],
+            ..Info::default()
+        };
+
+        block_on(async {
+            let (reply, served) = join(
+                proxy.monitor(),
+                serve_next::<_, _, FtlError>(&mut handler, &mut server, &info),
+            )
+            .await;
+            served.unwrap();
+            assert_eq!(reply.unwrap().condition.state, DriveConditionState::Idle);
+
+            let (reply, served) = join(
+                proxy.jump(None),
+                serve_next::<_, _, FtlError>(&mut handler, &mut server, &info),
+            )
+            .await;
+            served.unwrap();
+            reply.unwrap();
+            assert_eq!(handler.0.condition.state, DriveConditionState::Busy);
+
+            let current = Coordinate {
+                longitude: 0.0,
+                latitude: 0.0,
+                distance: 0,
+            };
+            let target = Coordinate {
+                distance: -1,
+                ..current.clone()
+            };
+            let (reply, served) = join(
+                proxy.calculate_configuration(current, target),
+                serve_next::<_, _, FtlError>(&mut handler, &mut server, &info),
+            )
+            .await;
+            served.unwrap();
+            match reply {
+                Err(zlink::Error::Reply(FtlError::ParameterOutOfRange { field })) => {
+                    assert_eq!(field, "distance")
+                }
+                r => panic!("unexpected reply: {r:?}"),
+            }
+        });
+    }
+
+    #[test]
+    fn more() {
+        let channel = Channel::new();
+        let (client, server) = channel.split();
+        let (mut client, mut server) = (Connection::new(client), Connection::new(server));
+        let mut handler = FtlHandler(Drive {
+            condition: DriveCondition {
+                state: DriveConditionState::Idle,
+                tylium_level: 100,
+            },
+            caller: None,
+        });
+        let info = Info::default();
+
+        block_on(async {
+            let receive = async {
+                let mut replies = client
+                    .call_method_more::<_, FtlError>(FtlMethodCall::Monitor)
+                    .await
+                    .unwrap();
+                let mut levels = Vec::new();
+                while let Some(reply) = replies.next::<MonitorReply, FtlError>().await {
+                    levels.push(
+                        reply
+                            .unwrap()
+                            .into_parameters()
+                            .unwrap()
+                            .condition
+                            .tylium_level,
+                    );
+                }
+                assert_eq!(levels, [100, 90, 80]);
+
+                // Methods without their own `more` handling reply once.
+                let current = Coordinate {
+                    longitude: 0.0,
+                    latitude: 0.0,
+                    distance: 0,
+                };
+                let call = FtlMethodCall::CalculateConfiguration {
+                    current: current.clone(),
+                    target: current,
+                };
+                let mut replies = client.call_method_more::<_, FtlError>(call).await.unwrap();
+                let reply = replies
+                    .next::<CalculateConfigurationReply, FtlError>()
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(reply.continues(), None);
+                assert!(replies.is_done());
+            };
+            let serve = async {
+                for _ in 0..2 {
+                    serve_next::<_, _, FtlError>(&mut handler, &mut server, &info)
+                        .await
+                        .unwrap();
+                }
+            };
+            join(receive, serve).await;
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn credentials() {
+        use std::sync::Arc;
+
+        use zlink::{
+            connection::{Call, Credentials},
+            server::Service,
+        };
+
+        let mut handler = FtlHandler(Drive {
+            condition: DriveCondition {
+                state: DriveConditionState::Idle,
+                tylium_level: 100,
+            },
+            caller: None,
+        });
+        let mut call = Call::new(FtlMethodCall::Monitor, None, None, None);
+        call.set_peer_credentials(Some(Arc::new(Credentials::new(1000, 100, None, None))));
+        block_on(handler.handle(call));
+        assert_eq!(handler.0.caller, Some(1000));
+    }
+
+    #[derive(Debug)]
+    struct Drive {
+        condition: DriveCondition,
+        // The user ID of the last caller.
+        caller: Option<u32>,
+    }
+
+    impl FtlService for Drive {
+        #[cfg(unix)]
+        fn on_call(&mut self, call: &mut zlink::connection::Call<FtlMethodCall>) {
+            self.caller = call.peer_credentials().map(|c| c.uid());
+        }
+
+        async fn monitor(&mut self) -> Result<MonitorReply, FtlError> {
+            Ok(MonitorReply {
+                condition: self.condition.clone(),
+            })
+        }
+
+        async fn monitor_more(&mut self) -> Result<FtlReplies<MonitorReply>, FtlError> {
+            let condition = self.condition.clone();
+            let replies = (0..3).map(move |i| MonitorReply {
+                condition: DriveCondition {
+                    tylium_level: condition.tylium_level - i * 10,
+                    ..condition.clone()
+                },
+            });
+
+            Ok(Box::pin(futures_util::stream::iter(replies)))
+        }
+
+        async fn calculate_configuration(
+            &mut self,
+            current: Coordinate,
+            target: Coordinate,
+        ) -> Result<CalculateConfigurationReply, FtlError> {
+            if target.distance < 0 {
+                return Err(FtlError::ParameterOutOfRange {
+                    field: "distance".into(),
+                });
+            }
+
+            Ok(CalculateConfigurationReply {
+                configuration: serde_json::json!({
+                    "distance": target.distance - current.distance,
+                }),
+            })
+        }
+
+        async fn jump(&mut self, _config: Option<serde_json::Value>) -> Result<(), FtlError> {
+            if self.condition.tylium_level < 10 {
+                return Err(FtlError::NotEnoughEnergy);
+            }
+            self.condition.state = DriveConditionState::Busy;
+
+            Ok(())
+        }
+    }
+}
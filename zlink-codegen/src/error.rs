@@ -0,0 +1,67 @@
+/// The Error type for the zlink-codegen crate.
+#[derive(Debug)]
+pub enum Error {
+    /// Error parsing the interface definition.
+    Parse(zlink::idl::ParseError),
+    /// The generated code is invalid.
+    ///
+    /// This is a bug in zlink-codegen and should be reported.
+    Syntax(syn::Error),
+    /// The same type name is used by different types.
+    ///
+    /// Anonymous types are named after their context, e.g `JumpConfig` for the type of the
+    /// `config` argument of the `Jump` method, which may collide with a declared type.
+    DuplicateType(String),
+    /// The `OUT_DIR` environment variable is not set.
+    ///
+    /// [`compile`](crate::compile) must be called from a build script.
+    OutDirNotSet,
+    /// An I/O error.
+    Io(std::io::Error),
+}
+
+/// The Result type for the zlink-codegen crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::Syntax(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::DuplicateType(_) | Error::OutDirNotSet => None,
+        }
+    }
+}
+
+impl From<zlink::idl::ParseError> for Error {
+    fn from(e: zlink::idl::ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<syn::Error> for Error {
+    fn from(e: syn::Error) -> Self {
+        Error::Syntax(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "Error parsing the interface definition: {e}"),
+            Error::Syntax(e) => write!(f, "Generated code is invalid: {e}"),
+            Error::DuplicateType(name) => {
+                write!(f, "Type name `{name}` is used by different types")
+            }
+            Error::OutDirNotSet => write!(f, "`OUT_DIR` environment variable is not set"),
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+        }
+    }
+}
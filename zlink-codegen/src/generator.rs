@@ -0,0 +1,669 @@
+use std::collections::HashMap;
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+use zlink::idl::{EnumVariant, Field, Interface, Method, Type};
+
+use crate::{Error, Result};
+
+// Generates the code for an interface.
+pub(crate) struct Generator<'g> {
+    interface: &'g Interface<'g>,
+    // The prefix of the interface-wide types, e.g `Ftl` for `org.example.ftl`.
+    prefix: String,
+    // The generated items.
+    items: Vec<TokenStream>,
+    // The names of the types defined so far, along with the anonymous type they were defined for.
+    defined_types: HashMap<String, Option<Type<'g>>>,
+    // The first type name found to be used by different types.
+    duplicate_type: Option<String>,
+}
+
+impl<'g> Generator<'g> {
+    pub(crate) fn new(interface: &'g Interface<'g>) -> Self {
+        let prefix = interface
+            .name
+            .rsplit('.')
+            .next()
+            .map(|name| to_pascal_case(&name.replace('-', "_")))
+            .unwrap_or_default();
+
+        Self {
+            interface,
+            prefix,
+            items: Vec::new(),
+            defined_types: HashMap::new(),
+            duplicate_type: None,
+        }
+    }
+
+    pub(crate) fn generate(mut self) -> Result<TokenStream> {
+        let interface = self.interface;
+        // The types generated for the interface itself.
+        for suffix in [
+            "",
+            "MethodCall",
+            "Error",
+            "Proxy",
+            "Service",
+            "Handler",
+            "Reply",
+            "Replies",
+            "ReplyStream",
+        ] {
+            self.define(&format!("{}{suffix}", self.prefix), None);
+        }
+        for ty in &interface.types {
+            self.type_definition(ty.name, &ty.ty, &ty.comments, false);
+        }
+        for method in &interface.methods {
+            let name = format!("{}Reply", method.name);
+            if !method.outputs.is_empty() && self.define(&name, None) {
+                self.struct_definition(&name, &method.outputs, &method.comments);
+            }
+        }
+        self.method_call_enum();
+        self.error_enum();
+        self.proxy();
+        self.service();
+
+        if let Some(name) = self.duplicate_type {
+            return Err(Error::DuplicateType(name));
+        }
+        let items = &self.items;
+        Ok(quote!(#(#items)*))
+    }
+
+    // Registers the type `name`, returning `false` if it's already defined.
+    //
+    // Anonymous types are defined each time a field of theirs is encountered, so they are given as
+    // `anonymous` to tell them apart from a different type with the same name, which is an error.
+    fn define(&mut self, name: &str, anonymous: Option<&Type<'g>>) -> bool {
+        match self.defined_types.get(name) {
+            None => {
+                self.defined_types
+                    .insert(name.to_string(), anonymous.cloned());
+
+                true
+            }
+            Some(Some(ty)) if Some(ty) == anonymous => false,
+            Some(_) => {
+                self.duplicate_type.get_or_insert_with(|| name.to_string());
+
+                false
+            }
+        }
+    }
+
+    fn type_definition(&mut self, name: &str, ty: &Type<'g>, comments: &[&str], anonymous: bool) {
+        if !self.define(name, anonymous.then_some(ty)) {
+            return;
+        }
+
+        match ty {
+            Type::Struct(fields) => self.struct_definition(name, fields, comments),
+            Type::Enum(variants) => self.enum_definition(name, variants, comments),
+            ty => {
+                let ident = format_ident!("{name}");
+                let ty = self.rust_type(ty, name);
+                let docs = docs(comments);
+                self.items.push(quote! {
+                    #docs
+                    pub type #ident = #ty;
+                });
+            }
+        }
+    }
+
+    fn struct_definition(&mut self, name: &str, fields: &[Field<'g>], comments: &[&str]) {
+        let ident = format_ident!("{name}");
+        let fields = self.fields(name, fields, true);
+        let docs = docs(comments);
+        self.items.push(quote! {
+            #docs
+            #[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+            pub struct #ident {
+                #(#fields),*
+            }
+        });
+    }
+
+    fn enum_definition(&mut self, name: &str, variants: &[EnumVariant<'_>], comments: &[&str]) {
+        let ident = format_ident!("{name}");
+        let variants = variants.iter().map(|variant| {
+            let docs = docs(&variant.comments);
+            let name = variant.name;
+            let ident = format_ident!("{}", to_pascal_case(name));
+            quote! {
+                #docs
+                #[serde(rename = #name)]
+                #ident
+            }
+        });
+        let docs = docs(comments);
+        self.items.push(quote! {
+            #docs
+            #[derive(
+                Debug,
+                Clone,
+                Copy,
+                PartialEq,
+                Eq,
+                Hash,
+                ::serde::Serialize,
+                ::serde::Deserialize,
+            )]
+            pub enum #ident {
+                #(#variants),*
+            }
+        });
+    }
+
+    fn method_call_enum(&mut self) {
+        let interface = self.interface;
+        let mut variants = Vec::new();
+        for method in &interface.methods {
+            let name = format!("{}.{}", interface.name, method.name);
+            let ident = format_ident!("{}", method.name);
+            let docs = docs(&method.comments);
+            if method.inputs.is_empty() {
+                variants.push(quote! {
+                    #docs
+                    #[serde(rename = #name, deserialize_with = #EMPTY_PARAMETERS)]
+                    #ident
+                });
+                continue;
+            }
+            let fields = self.fields(method.name, &method.inputs, false);
+
+            variants.push(quote! {
+                #docs
+                #[serde(rename = #name)]
+                #ident { #(#fields),* }
+            });
+        }
+
+        let ident = self.method_call_ident();
+        let doc = format!(" Method calls of the `{}` interface.", interface.name);
+        self.items.push(quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+            #[serde(tag = "method", content = "parameters")]
+            pub enum #ident {
+                #(#variants),*
+            }
+        });
+    }
+
+    fn error_enum(&mut self) {
+        let interface = self.interface;
+        let ident = self.error_ident();
+        let mut variants = Vec::new();
+        let mut display_arms = Vec::new();
+        for error in &interface.errors {
+            let name = format!("{}.{}", interface.name, error.name);
+            let variant = format_ident!("{}", error.name);
+            let docs = docs(&error.comments);
+            let message = to_sentence(error.name);
+            if error.fields.is_empty() {
+                variants.push(quote! {
+                    #docs
+                    #[serde(rename = #name, deserialize_with = #EMPTY_PARAMETERS)]
+                    #variant
+                });
+                display_arms.push(quote!(#ident::#variant => f.write_str(#message)));
+                continue;
+            }
+            let fields = self.fields(error.name, &error.fields, false);
+            variants.push(quote! {
+                #docs
+                #[serde(rename = #name)]
+                #variant { #(#fields),* }
+            });
+
+            // E.g `Parameter out of range (field: "speed")`.
+            let field_idents: Vec<_> = error.fields.iter().map(|f| field_ident(f.name)).collect();
+            let field_formats: Vec<_> = error
+                .fields
+                .iter()
+                .map(|f| format!("{}: {{:?}}", f.name))
+                .collect();
+            let format = format!("{message} ({})", field_formats.join(", "));
+            display_arms.push(quote! {
+                #ident::#variant { #(#field_idents),* } => write!(f, #format, #(#field_idents),*)
+            });
+        }
+        // An interface without errors results in an empty enum.
+        let scrutinee = if display_arms.is_empty() {
+            quote!(*self)
+        } else {
+            quote!(self)
+        };
+
+        let doc = format!(" Errors of the `{}` interface.", interface.name);
+        self.items.push(quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+            #[serde(tag = "error", content = "parameters")]
+            pub enum #ident {
+                #(#variants),*
+            }
+
+            impl ::core::fmt::Display for #ident {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    match #scrutinee {
+                        #(#display_arms,)*
+                    }
+                }
+            }
+
+            impl ::std::error::Error for #ident {}
+        });
+    }
+
+    fn proxy(&mut self) {
+        let interface = self.interface;
+        let error = self.error_ident();
+        let mut methods = Vec::new();
+        for method in &interface.methods {
+            let name = method.name;
+            let ident = field_ident(method.name);
+            let docs = docs(&method.comments);
+            let args = method.inputs.iter().map(|field| {
+                let name = field.name;
+                let ident = field_ident(name);
+                let ty = self.rust_type(
+                    &field.ty,
+                    &format!("{}{}", method.name, to_pascal_case(name)),
+                );
+                let rename = (ident != name).then(|| quote!(#[zlink(rename = #name)]));
+
+                quote!(#rename #ident: #ty)
+            });
+            let args: Vec<_> = args.collect();
+            let reply = reply_type(method);
+
+            methods.push(quote! {
+                #docs
+                #[zlink(rename = #name)]
+                async fn #ident(&mut self, #(#args),*) -> ::zlink::Result<#reply, #error>;
+            });
+        }
+
+        let ident = format_ident!("{}", self.prefix);
+        let name = interface.name;
+        self.items.push(quote! {
+            #[::zlink::proxy(#name)]
+            pub trait #ident {
+                #(#methods)*
+            }
+        });
+    }
+
+    fn service(&mut self) {
+        let interface = self.interface;
+        let service = format_ident!("{}Service", self.prefix);
+        let handler = format_ident!("{}Handler", self.prefix);
+        let reply_enum = format_ident!("{}Reply", self.prefix);
+        let replies = format_ident!("{}Replies", self.prefix);
+        let reply_stream = format_ident!("{}ReplyStream", self.prefix);
+        let call_enum = self.method_call_ident();
+        let error = self.error_ident();
+
+        let mut methods = Vec::new();
+        let mut reply_variants = Vec::new();
+        let mut match_arms = Vec::new();
+        for method in &interface.methods {
+            let ident = field_ident(method.name);
+            let variant = format_ident!("{}", method.name);
+            let docs = docs(&method.comments);
+            let mut args = Vec::new();
+            let mut arg_idents = Vec::new();
+            for field in &method.inputs {
+                let arg = field_ident(field.name);
+                let context = format!("{}{}", method.name, to_pascal_case(field.name));
+                let ty = self.rust_type(&field.ty, &context);
+                args.push(quote!(#arg: #ty));
+                arg_idents.push(arg);
+            }
+            let reply = reply_type(method);
+
+            methods.push(quote! {
+                #docs
+                fn #ident(
+                    &mut self,
+                    #(#args),*
+                ) -> impl ::core::future::Future<Output = ::core::result::Result<#reply, #error>>;
+            });
+
+            let pattern = if arg_idents.is_empty() {
+                quote!(#call_enum::#variant)
+            } else {
+                quote!(#call_enum::#variant { #(#arg_idents),* })
+            };
+            let ok_arm = if method.outputs.is_empty() {
+                quote!(Ok(()) => ::zlink::server::MethodReply::Single(None))
+            } else {
+                let ident_more = format_ident!("{ident}_more");
+                let more_doc = format!(
+                    " Handle `{}` calls with the `more` flag set, replying with each item of the \
+                     returned stream.",
+                    method.name,
+                );
+                let more_doc2 = format!(" By default, replies once with the result of `{ident}`.");
+                methods.push(quote! {
+                    #[doc = #more_doc]
+                    #[doc = ""]
+                    #[doc = #more_doc2]
+                    fn #ident_more(
+                        &mut self,
+                        #(#args),*
+                    ) -> impl ::core::future::Future<
+                        Output = ::core::result::Result<#replies<#reply>, #error>,
+                    > {
+                        async move {
+                            let reply = self.#ident(#(#arg_idents),*).await?;
+                            let replies: #replies<#reply> = ::std::boxed::Box::pin(
+                                ::zlink::__private::futures_util::stream::once(
+                                    ::core::future::ready(reply),
+                                ),
+                            );
+
+                            Ok(replies)
+                        }
+                    }
+                });
+                match_arms.push(quote! {
+                    #pattern if more => match self.0.#ident_more(#(#arg_idents),*).await {
+                        Ok(replies) => ::zlink::server::MethodReply::Multi(#reply_stream(
+                            ::std::boxed::Box::pin(
+                                ::zlink::__private::futures_util::StreamExt::map(
+                                    replies,
+                                    |reply| ::zlink::connection::Reply::new(
+                                        Some(#reply_enum::#variant(reply)),
+                                        None,
+                                    ),
+                                ),
+                            ),
+                        )),
+                        Err(e) => ::zlink::server::MethodReply::Error(e),
+                    }
+                });
+
+                reply_variants.push(quote!(#variant(#reply)));
+                quote! {
+                    Ok(reply) => ::zlink::server::MethodReply::Single(Some(#reply_enum::#variant(reply)))
+                }
+            };
+            match_arms.push(quote! {
+                #pattern => match self.0.#ident(#(#arg_idents),*).await {
+                    #ok_arm,
+                    Err(e) => ::zlink::server::MethodReply::Error(e),
+                }
+            });
+        }
+
+        let name = interface.name;
+        let service_doc = format!(" Implementation of the `{name}` interface.");
+        let service_doc2 = format!(
+            " Wrap the implementation in [`{handler}`] to serve it through `zlink::Server`."
+        );
+        let handler_doc =
+            format!(" Wraps a [`{service}`] implementation to implement `zlink::server::Service`.");
+        // Only methods with output parameters can reply more than once.
+        let more = interface
+            .methods
+            .iter()
+            .any(|method| !method.outputs.is_empty())
+            .then(|| quote!(let more = call.more().unwrap_or(false);));
+        let reply_doc = format!(" Replies of the `{name}` interface.");
+        let replies_doc = format!(
+            " A stream of replies to a method call of the `{name}` interface with the `more` flag \
+             set."
+        );
+        let reply_stream_doc =
+            format!(" The replies of [`{handler}`] to method calls with the `more` flag set.");
+        self.items.push(quote! {
+            #[doc = #service_doc]
+            #[doc = ""]
+            #[doc = #service_doc2]
+            pub trait #service {
+                /// Called with each method call before it's handled.
+                ///
+                /// This gives access to the metadata of the call, e.g the credentials of the
+                /// caller through `zlink::connection::Call::peer_credentials`. Does nothing by
+                /// default.
+                fn on_call(&mut self, call: &mut ::zlink::connection::Call<#call_enum>) {
+                    let _ = call;
+                }
+
+                #(#methods)*
+            }
+
+            #[doc = #handler_doc]
+            #[derive(Debug)]
+            pub struct #handler<T>(pub T);
+
+            #[doc = #reply_doc]
+            #[derive(Debug, Clone, PartialEq, ::serde::Serialize)]
+            #[serde(untagged)]
+            pub enum #reply_enum {
+                #(#reply_variants),*
+            }
+
+            #[doc = #replies_doc]
+            pub type #replies<T> = ::core::pin::Pin<
+                ::std::boxed::Box<dyn ::zlink::__private::futures_util::Stream<Item = T>>,
+            >;
+
+            #[doc = #reply_stream_doc]
+            pub struct #reply_stream(#replies<::zlink::connection::Reply<#reply_enum>>);
+
+            impl ::core::fmt::Debug for #reply_stream {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.debug_struct(stringify!(#reply_stream)).finish_non_exhaustive()
+                }
+            }
+
+            impl ::zlink::__private::futures_util::Stream for #reply_stream {
+                type Item = ::zlink::connection::Reply<#reply_enum>;
+
+                fn poll_next(
+                    self: ::core::pin::Pin<&mut Self>,
+                    cx: &mut ::core::task::Context<'_>,
+                ) -> ::core::task::Poll<::core::option::Option<Self::Item>> {
+                    ::zlink::__private::futures_util::Stream::poll_next(self.get_mut().0.as_mut(), cx)
+                }
+            }
+
+            impl<T: #service> ::zlink::server::Service for #handler<T> {
+                type MethodCall<'de> = #call_enum;
+                type Reply<'ser> = #reply_enum
+                where
+                    Self: 'ser;
+                type ReplyStream = #reply_stream;
+                type ReplyStreamParams = #reply_enum;
+                type ReplyError<'ser> = #error
+                where
+                    Self: 'ser;
+
+                async fn handle<'ser>(
+                    &'ser mut self,
+                    call: ::zlink::connection::Call<Self::MethodCall<'_>>,
+                ) -> ::zlink::server::MethodReply<
+                    Self::Reply<'ser>,
+                    Self::ReplyStream,
+                    Self::ReplyError<'ser>,
+                > {
+                    let mut call = call;
+                    self.0.on_call(&mut call);
+                    #more
+                    match call.into_method() {
+                        #(#match_arms,)*
+                    }
+                }
+            }
+        });
+    }
+
+    // Generates the fields of a struct or a struct variant of an enum.
+    //
+    // Anonymous types of the fields are named after `context` and the field name.
+    fn fields(&mut self, context: &str, fields: &[Field<'g>], public: bool) -> Vec<TokenStream> {
+        let mut tokens = Vec::new();
+        for field in fields {
+            let name = field.name;
+            let ident = field_ident(name);
+            let ty = self.rust_type(&field.ty, &format!("{context}{}", to_pascal_case(name)));
+            let docs = docs(&field.comments);
+            let vis = public.then(|| quote!(pub));
+
+            let mut serde_attrs = Vec::new();
+            if ident != name {
+                serde_attrs.push(quote!(rename = #name));
+            }
+            if matches!(field.ty, Type::Optional(_)) {
+                serde_attrs.push(quote!(default, skip_serializing_if = "Option::is_none"));
+            }
+            let serde_attrs =
+                (!serde_attrs.is_empty()).then(|| quote!(#[serde(#(#serde_attrs),*)]));
+
+            tokens.push(quote! {
+                #docs
+                #serde_attrs
+                #vis #ident: #ty
+            });
+        }
+
+        tokens
+    }
+
+    // The Rust type for a Varlink type.
+    //
+    // Anonymous types are defined with `context` as their name.
+    fn rust_type(&mut self, ty: &Type<'g>, context: &str) -> TokenStream {
+        match ty {
+            Type::Bool => quote!(bool),
+            Type::Int => quote!(i64),
+            Type::Float => quote!(f64),
+            Type::String => quote!(::std::string::String),
+            Type::Object => quote!(::serde_json::Value),
+            Type::Custom(name) => {
+                let ident = format_ident!("{name}");
+                quote!(#ident)
+            }
+            Type::Optional(ty) => {
+                let ty = self.rust_type(ty, context);
+                quote!(::std::option::Option<#ty>)
+            }
+            Type::Array(ty) => {
+                let ty = self.rust_type(ty, context);
+                quote!(::std::vec::Vec<#ty>)
+            }
+            Type::Map(ty) => {
+                let ty = self.rust_type(ty, context);
+                quote!(::std::collections::HashMap<::std::string::String, #ty>)
+            }
+            Type::Enum(_) | Type::Struct(_) => {
+                self.type_definition(context, ty, &[], true);
+                let ident = format_ident!("{context}");
+                quote!(#ident)
+            }
+        }
+    }
+
+    fn method_call_ident(&self) -> Ident {
+        format_ident!("{}MethodCall", self.prefix)
+    }
+
+    fn error_ident(&self) -> Ident {
+        format_ident!("{}Error", self.prefix)
+    }
+}
+
+fn reply_type(method: &Method<'_>) -> TokenStream {
+    if method.outputs.is_empty() {
+        quote!(())
+    } else {
+        let ident = format_ident!("{}Reply", method.name);
+        quote!(#ident)
+    }
+}
+
+fn docs(comments: &[&str]) -> TokenStream {
+    let docs = comments.iter().map(|comment| {
+        let comment = format!(" {comment}");
+        quote!(#[doc = #comment])
+    });
+
+    quote!(#(#docs)*)
+}
+
+// The Rust identifier for a field, argument or method name.
+fn field_ident(name: &str) -> Ident {
+    let name = to_snake_case(name);
+    match name.as_str() {
+        "self" | "super" | "crate" => format_ident!("{name}_"),
+        name if KEYWORDS.contains(&name) => Ident::new_raw(name, Span::call_site()),
+        name => format_ident!("{name}"),
+    }
+}
+
+fn to_snake_case(name: &str) -> String {
+    let chars: Vec<_> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len());
+    for (i, c) in chars.iter().enumerate() {
+        if !c.is_ascii_uppercase() {
+            snake.push(*c);
+            continue;
+        }
+
+        // Start a new word at the start of a capitalized word (`fooBar`) or at the end of an
+        // acronym (`DNSError`).
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let new_word = match prev {
+            Some(prev) if prev.is_ascii_lowercase() || prev.is_ascii_digit() => true,
+            Some(prev) if prev.is_ascii_uppercase() => next.is_some_and(|n| n.is_ascii_lowercase()),
+            _ => false,
+        };
+        if new_word && !snake.ends_with('_') {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+
+    snake
+}
+
+// A sentence from a Pascal-cased name, e.g `Not enough energy` for `NotEnoughEnergy`.
+fn to_sentence(name: &str) -> String {
+    let words = to_snake_case(name).replace('_', " ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+const EMPTY_PARAMETERS: &str = "::zlink::connection::empty_parameters";
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
@@ -0,0 +1,193 @@
+#![deny(
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_idioms,
+    missing_docs
+)]
+#![warn(unreachable_pub)]
+//! Generates Rust code for [zlink](https://docs.rs/zlink) from Varlink interface definitions.
+//!
+//! For each interface, the following code is generated:
+//!
+//! * A type for each custom type declared in the interface.
+//! * `<Interface>MethodCall` enum for all the method calls of the interface.
+//! * `<Method>Reply` type for the reply parameters of each method with output parameters.
+//! * `<Interface>Error` enum for all the errors of the interface.
+//! * `<Interface>Proxy` type for calling methods of the interface, generated through the
+//!   `zlink::proxy` macro.
+//! * `<Interface>Service` trait to implement the interface and `<Interface>Handler` type that wraps
+//!   an implementation of the trait to implement `zlink::server::Service`. Implement the
+//!   `on_call` method of the trait to access the metadata of the method calls, e.g the credentials
+//!   of the caller. For each method with output parameters, the trait also has a `<method>_more`
+//!   method handling the calls with the `more` flag set. It returns an `<Interface>Replies` stream,
+//!   each item of which is sent as a reply. By default, it replies once with the result of the
+//!   method.
+//! * `DESCRIPTION` constant containing the IDL of the interface, to be passed to
+//!   `zlink::Server` through `zlink::varlink_service::Info`.
+//!
+//! where `<Interface>` is the last component of the interface name. The generated code requires
+//! the `zlink`, `serde` and `serde_json` crates to be a dependency of the crate using it.
+//!
+//! The easiest way to use this crate is from a build script:
+//!
+//! ```no_run
+//! // build.rs
+//! fn main() {
+//!     zlink_codegen::compile(&["src/org.example.ftl.varlink"]).unwrap();
+//! }
+//! ```
+//!
+//! and then include the generated code:
+//!
+//! ```ignore
+//! mod ftl {
+//!     include!(concat!(env!("OUT_DIR"), "/org_example_ftl.rs"));
+//! }
+//! ```
+
+mod error;
+pub use error::{Error, Result};
+mod generator;
+
+use std::{env, fs, path::Path};
+
+use zlink::idl::Interface;
+
+/// Generate the code for an interface from its IDL.
+pub fn generate(idl: &str) -> Result<String> {
+    let interface = Interface::parse(idl)?;
+    let mut tokens = generator::Generator::new(&interface).generate()?;
+    let doc = format!(
+        " The IDL description of the `{}` interface.",
+        interface.name
+    );
+    tokens.extend(quote::quote! {
+        #[doc = #doc]
+        pub const DESCRIPTION: &str = #idl;
+    });
+    let file = syn::parse2(tokens)?;
+
+    Ok(format!(
+        "// This file was generated by zlink-codegen from the `{}` interface. Do not edit.\n\n{}",
+        interface.name,
+        prettyplease::unparse(&file),
+    ))
+}
+
+/// Generate the code for the interface in the `input` IDL file and write it to the `output` file.
+pub fn generate_file<I, O>(input: I, output: O) -> Result<()>
+where
+    I: AsRef<Path>,
+    O: AsRef<Path>,
+{
+    let idl = fs::read_to_string(input)?;
+    let code = generate(&idl)?;
+
+    fs::write(output, code).map_err(Into::into)
+}
+
+/// Generate the code for the interfaces in the given IDL files from a build script.
+///
+/// The code for each interface is written to `$OUT_DIR/<interface>.rs`, where `<interface>` is
+/// the interface name with `.` and `-` replaced by `_`, e.g `org_example_ftl.rs` for the
+/// `org.example.ftl` interface. Cargo is instructed to rerun the build script if any of the files
+/// change.
+pub fn compile<P>(files: &[P]) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let out_dir = env::var_os("OUT_DIR").ok_or(Error::OutDirNotSet)?;
+    for file in files {
+        let file = file.as_ref();
+        println!("cargo:rerun-if-changed={}", file.display());
+
+        let idl = fs::read_to_string(file)?;
+        let name = Interface::parse(&idl)?.name.replace(['.', '-'], "_");
+        let code = generate(&idl)?;
+        fs::write(Path::new(&out_dir).join(format!("{name}.rs")), code)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn generate() {
+        let code = super::generate(
+            r#"
+# Interface to jump a spacecraft to another point in space.
+interface org.example.ftl
+
+type DriveCondition (
+  state: (idle, spooling, busy),
+  tylium_level: int
+)
+
+type Coordinate (
+  longitude: float,
+  latitude: float,
+  distance: int
+)
+
+# Monitor the drive.
+method Monitor() -> (condition: DriveCondition)
+method CalculateConfiguration(current: Coordinate, target: Coordinate) -> (configuration: object)
+method Jump(config: ?object) -> ()
+
+error NotEnoughEnergy ()
+error ParameterOutOfRange (field: string)
+"#,
+        )
+        .unwrap();
+
+        for snippet in [
+            "pub struct DriveCondition {",
+            "pub state: DriveConditionState,",
+            "pub enum DriveConditionState {",
+            "#[serde(rename = \"idle\")]",
+            "pub struct MonitorReply {",
+            "pub enum FtlMethodCall {",
+            "#[serde(rename = \"org.example.ftl.Monitor\")]",
+            "Monitor,",
+            "#[serde(default, skip_serializing_if = \"Option::is_none\")]",
+            "config: ::std::option::Option<::serde_json::Value>,",
+            "pub enum FtlError {",
+            "#[::zlink::proxy(\"org.example.ftl\")]",
+            "async fn calculate_configuration(",
+            "pub trait FtlService {",
+            "pub struct FtlHandler<T>(pub T);",
+            "fn monitor_more(",
+            "pub struct FtlReplyStream(",
+            "/// Monitor the drive.",
+            "pub const DESCRIPTION: &str = ",
+        ] {
+            assert!(code.contains(snippet), "`{snippet}` not found in:\n{code}");
+        }
+    }
+
+    #[test]
+    fn duplicate_type() {
+        // The anonymous type of `config` is named `JumpConfig` as well.
+        let result = super::generate(
+            r#"
+interface org.example.ftl
+
+type JumpConfig (speed: int)
+
+method Jump(config: (distance: int)) -> ()
+"#,
+        );
+        assert!(
+            matches!(&result, Err(super::Error::DuplicateType(name)) if name == "JumpConfig"),
+            "unexpected result: {result:?}"
+        );
+
+        // Types can't be named like the ones generated for the interface either.
+        let result = super::generate("interface org.example.ftl\n\ntype FtlError (code: int)\n");
+        assert!(
+            matches!(&result, Err(super::Error::DuplicateType(name)) if name == "FtlError"),
+            "unexpected result: {result:?}"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+#![deny(
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_idioms,
+    missing_docs
+)]
+#![warn(unreachable_pub)]
+//! Macros for the [zlink](https://docs.rs/zlink) crate. You don't want to use this crate directly
+//! but rather use the macros through `zlink` itself.
+
+mod proxy;
+mod service;
+mod utils;
+
+/// Implements the `zlink::server::Service` trait for the type of an inherent `impl` block.
+///
+/// Each `async` method of the `impl` block that takes `self` by reference becomes a method of
+/// the service. The method name is the name of the Rust method converted to pascal case, prefixed
+/// with the interface name. The arguments of the Rust method become the method parameters and its
+/// return value the reply. If the return type is a `Result`, its error type is used for the error
+/// replies.
+///
+/// The macro also generates the `<Type>MethodCall`, `<Type>Reply` and `<Type>ReplyError` enums,
+/// which are used as the associated types of the `Service` implementation, as well as the
+/// `<Type>ReplyStream` and `<Type>ReplyStreamParams` enums if any method has the `more`
+/// sub-attribute.
+///
+/// The macro doesn't generate the interface description of the service. To answer
+/// `org.varlink.service.GetInterfaceDescription` calls, pass the descriptions through
+/// `zlink::varlink_service::Info::descriptions`.
+///
+/// The macro supports the following sub-attributes:
+///
+/// * `interface`: The interface name. If this is given than all the methods will be prefixed with
+///   the interface name. This is useful when the service only offers a single interface.
+/// * `crate`: The path to the `zlink` crate. This is useful if you use `zlink` through a
+///   re-export, e.g `#[zlink::service(crate = "zlink_tokio")]`. Defaults to `::zlink`.
+///
+/// The methods support the following sub-attributes through `#[zlink(...)]`:
+///
+/// * `interface`: The interface name of this method, overriding the one given to the `service`
+///   attribute.
+/// * `rename`: The name of the method, without the interface name.
+/// * `more`: The method returns multiple replies. Instead of the reply, the method returns a
+///   `futures_util::Stream` of replies (or a `Result` of it), which must be `Unpin` and can't
+///   borrow from the service. Each item of the stream is sent as a reply.
+///
+/// The method arguments support the following sub-attributes through `#[zlink(...)]`:
+///
+/// * `credentials`: The argument isn't a method parameter but receives the credentials of the
+///   caller, as an `Option<&zlink::connection::Credentials>` (see
+///   `zlink::connection::Call::peer_credentials`). This is only supported on Unix, with the `std`
+///   feature of `zlink`.
+/// * `fds`: The argument isn't a method parameter but receives the file descriptors passed along
+///   with the method call, as a `Vec<std::os::fd::OwnedFd>` (see `zlink::connection::Call::fds`).
+///   The method parameters can refer to them by their index, using `zlink::connection::FdIndex`.
+///   This is only supported on Unix, with the `std` feature of `zlink`.
+///
+/// # Example
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+///
+/// struct Ftl {
+///     drive_condition: DriveCondition,
+/// }
+///
+/// #[zlink::service(interface = "org.example.ftl")]
+/// impl Ftl {
+///     async fn monitor(&mut self) -> DriveCondition {
+///         self.drive_condition
+///     }
+///
+///     async fn set_tylium_level(&mut self, level: i64) -> Result<(), FtlError> {
+///         if level < 0 {
+///             return Err(FtlError::InvalidLevel);
+///         }
+///         self.drive_condition.tylium_level = level;
+///
+///         Ok(())
+///     }
+/// }
+///
+/// #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// struct DriveCondition {
+///     tylium_level: i64,
+/// }
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// #[serde(tag = "error", content = "parameters")]
+/// enum FtlError {
+///     #[serde(rename = "org.example.ftl.InvalidLevel")]
+///     InvalidLevel,
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn service(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    service::service(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generates a proxy type for calling the methods of a Varlink interface.
+///
+/// The attribute is applied to a trait declaring the methods of the interface, and takes the
+/// interface name as its argument. The trait itself is only used as a declaration: the macro
+/// replaces it with a `<Trait>Proxy` type that wraps a `zlink::Connection` and has a method for each
+/// method of the trait.
+///
+/// The methods must be `async`, take `&mut self` as the receiver and return a `Result<T, E>`,
+/// where `T` is the type of the reply parameters and `E` the error reply type (see
+/// `zlink::Connection::receive_reply` for the requirements on these types). The method name is the
+/// name of the Rust method converted to pascal case, prefixed with the interface name. The
+/// arguments become the method parameters. The generated methods return a `zlink::Result<T, E>`.
+///
+/// The macro supports the following sub-attributes:
+///
+/// * `crate`: The path to the `zlink` crate. This is useful if you use `zlink` through a
+///   re-export, e.g `#[zlink::proxy("org.example.ftl", crate = "zlink_tokio")]`. Defaults to
+///   `::zlink`.
+///
+/// The methods support the following sub-attributes through `#[zlink(...)]`:
+///
+/// * `rename`: The name of the method, without the interface name.
+/// * `more`: The method is called with the `more` flag set and returns multiple replies. Instead
+///   of the reply, the generated method returns a `<Trait><Method>Stream` type to receive the
+///   replies through.
+///
+/// The method arguments support the following sub-attributes through `#[zlink(...)]`:
+///
+/// * `rename`: The name of the parameter, if it's different from the name of the argument.
+///
+/// # Example
+///
+/// ```
+/// use serde::Deserialize;
+/// use zlink::{connection::Socket, Connection};
+///
+/// #[zlink::proxy("org.example.ftl")]
+/// trait Ftl {
+///     async fn get_drive_condition(&mut self) -> zlink::Result<DriveCondition, FtlError>;
+///     async fn jump(&mut self, destination: &str) -> zlink::Result<(), FtlError>;
+///     #[zlink(rename = "Monitor", more)]
+///     async fn monitor_drive_condition(
+///         &mut self,
+///     ) -> zlink::Result<DriveCondition, FtlError>;
+/// }
+///
+/// #[derive(Debug, Deserialize)]
+/// struct DriveCondition {
+///     tylium_level: i64,
+/// }
+///
+/// #[derive(Debug, Deserialize)]
+/// #[serde(tag = "error", content = "parameters")]
+/// enum FtlError {
+///     #[serde(rename = "org.example.ftl.UnknownDestination")]
+///     UnknownDestination { destination: String },
+/// }
+///
+/// async fn monitor<S: Socket>(connection: Connection<S>) -> zlink::Result<(), FtlError> {
+///     let mut proxy = FtlProxy::new(connection);
+///     let mut stream = proxy.monitor_drive_condition().await?;
+///     while let Some(condition) = stream.next().await {
+///         match condition {
+///             Ok(condition) => println!("Tylium level: {}", condition.tylium_level),
+///             Err(e) => eprintln!("Error: {e:?}"),
+///         }
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn proxy(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    proxy::proxy(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,337 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    ext::IdentExt, parse::ParseStream, parse::Parser, parse2, parse_quote, visit_mut::VisitMut,
+    Attribute, Error, FnArg, Ident, ItemTrait, LitStr, Pat, Path, Result, ReturnType, Token,
+    TraitItem, TraitItemFn, Type,
+};
+
+use crate::utils::{generics, is_unit, result_types, to_pascal_case, ElidedLifetimes};
+
+pub(crate) fn proxy(attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
+    let (interface, crate_path) = parse_args.parse2(attr)?;
+    let item_trait: ItemTrait = parse2(item)?;
+    if !item_trait.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &item_trait.generics,
+            "`proxy` attribute is not supported on generic traits",
+        ));
+    }
+
+    let vis = &item_trait.vis;
+    let trait_name = &item_trait.ident;
+    let proxy_name = format_ident!("{trait_name}Proxy");
+    let serde = quote!(#crate_path::__private::serde);
+    let serde_crate = serde.to_string();
+
+    let mut methods = Vec::new();
+    let mut streams = Vec::new();
+    for item in &item_trait.items {
+        let TraitItem::Fn(f) = item else {
+            return Err(Error::new_spanned(
+                item,
+                "only methods are supported in `proxy` traits",
+            ));
+        };
+        let method = Method::parse(f, &interface.value())?;
+        let attrs = &method.attrs;
+        let ident = &method.ident;
+        let variant = &method.variant;
+        let name = &method.name;
+        let inputs = &method.inputs;
+        let reply = &method.reply;
+        let error = &method.error;
+
+        let args: Vec<_> = method.params.iter().map(|param| &param.ident).collect();
+        let fields = method.params.iter().map(|param| {
+            let ident = &param.ident;
+            let ty = &param.ty;
+            match &param.rename {
+                Some(name) => quote!(#[serde(rename = #name)] #ident: #ty),
+                None => quote!(#ident: #ty),
+            }
+        });
+        let (fields, args) = if args.is_empty() {
+            (quote!(), quote!())
+        } else {
+            (quote!({ #(#fields),* }), quote!({ #(#args),* }))
+        };
+        let method_generics = generics(method.params_borrow, &parse_quote!('m));
+        let method_enum = quote! {
+            #[derive(Debug, #serde::Serialize)]
+            #[serde(crate = #serde_crate, tag = "method", content = "parameters")]
+            enum Method #method_generics {
+                #[serde(rename = #name)]
+                #variant #fields
+            }
+            let method = Method::#variant #args;
+        };
+
+        if method.more {
+            let stream_name = format_ident!("{trait_name}{variant}Stream");
+            // The stream may end with a reply without parameters, which is not yielded.
+            let next = if is_unit(reply) {
+                quote! {
+                    match self.0.next::<#serde::de::IgnoredAny, #error>().await? {
+                        Ok(reply) if reply.parameters().is_none() && reply.continues() != Some(true) => {
+                            None
+                        }
+                        reply => Some(reply.map(|_| ())),
+                    }
+                }
+            } else {
+                quote! {
+                    let reply = self.0.next().await?;
+
+                    #crate_path::__private::stream_reply_parameters(reply)
+                }
+            };
+            let doc = format!("The stream of replies to `{name}` method calls.");
+
+            streams.push(quote! {
+                #[doc = #doc]
+                #[derive(Debug)]
+                #vis struct #stream_name<'c, S: #crate_path::connection::Socket>(
+                    #crate_path::connection::ReplyStream<'c, S>,
+                );
+
+                impl<'c, S: #crate_path::connection::Socket> #stream_name<'c, S> {
+                    /// Receive the next reply.
+                    ///
+                    /// Returns `None` if all replies have already been received.
+                    pub async fn next(&mut self) -> Option<#crate_path::Result<#reply, #error>> {
+                        #next
+                    }
+                }
+            });
+            methods.push(quote! {
+                #(#attrs)*
+                pub async fn #ident(#inputs) -> #crate_path::Result<#stream_name<'_, S>, #error> {
+                    #method_enum
+
+                    self.connection
+                        .call_method_more(method)
+                        .await
+                        .map(#stream_name)
+                }
+            });
+        } else {
+            let body = if is_unit(reply) {
+                quote! {
+                    self.connection
+                        .call_method::<_, #serde::de::IgnoredAny, #error>(method)
+                        .await
+                        .map(|_| ())
+                }
+            } else {
+                quote! {
+                    self.connection
+                        .call_method(method)
+                        .await
+                        .and_then(#crate_path::__private::reply_parameters)
+                }
+            };
+
+            methods.push(quote! {
+                #(#attrs)*
+                pub async fn #ident(#inputs) -> #crate_path::Result<#reply, #error> {
+                    #method_enum
+
+                    #body
+                }
+            });
+        }
+    }
+
+    let doc = format!(
+        "Proxy for calling methods of the `{}` interface.",
+        interface.value()
+    );
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug)]
+        #vis struct #proxy_name<S: #crate_path::connection::Socket> {
+            connection: #crate_path::Connection<S>,
+        }
+
+        impl<S: #crate_path::connection::Socket> #proxy_name<S> {
+            /// Create a new proxy for the given connection.
+            pub fn new(connection: #crate_path::Connection<S>) -> Self {
+                Self { connection }
+            }
+
+            /// The underlying connection.
+            pub fn connection(&mut self) -> &mut #crate_path::Connection<S> {
+                &mut self.connection
+            }
+
+            /// Convert the proxy into the underlying connection.
+            pub fn into_connection(self) -> #crate_path::Connection<S> {
+                self.connection
+            }
+
+            #(#methods)*
+        }
+
+        impl<S: #crate_path::connection::Socket> From<#crate_path::Connection<S>> for #proxy_name<S> {
+            fn from(connection: #crate_path::Connection<S>) -> Self {
+                Self::new(connection)
+            }
+        }
+
+        #(#streams)*
+    })
+}
+
+fn parse_args(input: ParseStream<'_>) -> Result<(LitStr, Path)> {
+    let interface = input.parse::<LitStr>()?;
+    let mut crate_path = parse_quote!(::zlink);
+    while !input.is_empty() {
+        input.parse::<Token![,]>()?;
+        if input.is_empty() {
+            break;
+        }
+
+        let key = input.call(Ident::parse_any)?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse::<LitStr>()?;
+        if key == "crate" {
+            crate_path = value.parse()?;
+        } else {
+            return Err(Error::new_spanned(key, "unsupported `proxy` attribute"));
+        }
+    }
+
+    Ok((interface, crate_path))
+}
+
+// A method of the proxy.
+struct Method {
+    // The attributes to forward to the generated method (e.g doc comments).
+    attrs: Vec<Attribute>,
+    ident: Ident,
+    // The name of the enum variant for this method.
+    variant: Ident,
+    // The fully-qualified Varlink method name.
+    name: String,
+    // The inputs of the method, including the receiver.
+    inputs: TokenStream,
+    params: Vec<Param>,
+    params_borrow: bool,
+    reply: Type,
+    error: Type,
+    // If the method is called with the `more` flag set.
+    more: bool,
+}
+
+// A parameter of a method.
+struct Param {
+    ident: Ident,
+    // The type, with elided lifetimes replaced.
+    ty: Type,
+    // The name of the parameter, if it's different from the Rust argument name.
+    rename: Option<String>,
+}
+
+impl Method {
+    fn parse(f: &TraitItemFn, interface: &str) -> Result<Self> {
+        let sig = &f.sig;
+        if sig.asyncness.is_none() {
+            return Err(Error::new_spanned(sig, "proxy methods must be `async`"));
+        }
+        match sig.receiver() {
+            Some(receiver) if receiver.mutability.is_some() && receiver.reference.is_some() => (),
+            _ => {
+                return Err(Error::new_spanned(
+                    sig,
+                    "proxy methods must take `&mut self` as the receiver",
+                ))
+            }
+        }
+
+        let mut rename = None;
+        let mut more = false;
+        let mut attrs = Vec::new();
+        for attr in &f.attrs {
+            if !attr.path().is_ident("zlink") {
+                attrs.push(attr.clone());
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("more") {
+                    more = true;
+                } else {
+                    return Err(meta.error("unsupported `zlink` attribute"));
+                }
+
+                Ok(())
+            })?;
+        }
+        let method_name = rename.unwrap_or_else(|| to_pascal_case(&sig.ident.to_string()));
+
+        let mut lifetimes = ElidedLifetimes::new("'m");
+        let mut params = Vec::new();
+        let mut inputs = sig.inputs.clone();
+        for input in inputs.iter_mut().skip(1) {
+            let FnArg::Typed(arg) = input else {
+                continue;
+            };
+            let Pat::Ident(pat) = &*arg.pat else {
+                return Err(Error::new_spanned(
+                    &arg.pat,
+                    "only simple identifiers are supported as method arguments",
+                ));
+            };
+
+            let mut rename = None;
+            for attr in arg.attrs.iter().filter(|a| a.path().is_ident("zlink")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                    } else {
+                        return Err(meta.error("unsupported `zlink` attribute"));
+                    }
+
+                    Ok(())
+                })?;
+            }
+            arg.attrs.retain(|a| !a.path().is_ident("zlink"));
+
+            let mut ty = (*arg.ty).clone();
+            lifetimes.visit_type_mut(&mut ty);
+            params.push(Param {
+                ident: pat.ident.clone(),
+                ty,
+                rename,
+            });
+        }
+
+        let result = match &sig.output {
+            ReturnType::Type(_, ty) => result_types(ty)?,
+            ReturnType::Default => None,
+        };
+        let Some((reply, error)) = result else {
+            return Err(Error::new_spanned(
+                sig,
+                "proxy methods must return a `Result<T, E>`",
+            ));
+        };
+
+        Ok(Self {
+            attrs,
+            ident: sig.ident.clone(),
+            variant: format_ident!("{}", to_pascal_case(&sig.ident.to_string())),
+            name: format!("{interface}.{method_name}"),
+            inputs: quote!(#inputs),
+            params,
+            params_borrow: lifetimes.replaced,
+            reply,
+            error,
+            more,
+        })
+    }
+}
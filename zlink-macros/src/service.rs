@@ -0,0 +1,503 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    meta::parser, parse::Parser, parse2, parse_quote, visit_mut::VisitMut, Attribute, Error, FnArg,
+    Ident, ImplItem, ImplItemFn, ItemImpl, Lifetime, LitStr, Pat, Path, Result, ReturnType, Type,
+};
+
+use crate::utils::{generics, is_unit, result_types, to_pascal_case, ElidedLifetimes};
+
+pub(crate) fn service(attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
+    let mut interface = None;
+    let mut crate_path: Path = parse_quote!(::zlink);
+    parser(|meta| {
+        if meta.path.is_ident("interface") {
+            interface = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.path.is_ident("crate") {
+            crate_path = meta.value()?.parse::<LitStr>()?.parse()?;
+        } else {
+            return Err(meta.error("unsupported `service` attribute"));
+        }
+
+        Ok(())
+    })
+    .parse2(attr)?;
+
+    let mut item_impl: ItemImpl = parse2(item)?;
+    if let Some((_, path, _)) = &item_impl.trait_ {
+        return Err(Error::new_spanned(
+            path,
+            "`service` attribute is only supported on inherent `impl` blocks",
+        ));
+    }
+    if !item_impl.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &item_impl.generics,
+            "`service` attribute is not supported on generic `impl` blocks",
+        ));
+    }
+    let self_ty = &item_impl.self_ty;
+    let type_name = match &**self_ty {
+        Type::Path(path) => match path.path.segments.last() {
+            Some(segment) => segment.ident.clone(),
+            None => return Err(Error::new_spanned(self_ty, "expected a type name")),
+        },
+        _ => return Err(Error::new_spanned(self_ty, "expected a type name")),
+    };
+
+    let mut methods = Vec::new();
+    for item in &mut item_impl.items {
+        if let ImplItem::Fn(f) = item {
+            if let Some(method) = Method::parse(f, interface.as_deref())? {
+                methods.push(method);
+            }
+        }
+    }
+
+    let call_lifetime = Lifetime::new("'m", Span::call_site());
+    let reply_lifetime = Lifetime::new("'ser", Span::call_site());
+    let call_enum = format_ident!("{type_name}MethodCall");
+    let reply_enum = format_ident!("{type_name}Reply");
+    let error_enum = format_ident!("{type_name}ReplyError");
+    let stream_enum = format_ident!("{type_name}ReplyStream");
+    let stream_params_enum = format_ident!("{type_name}ReplyStreamParams");
+    let call_generics = generics(
+        methods.iter().any(|m| m.params.iter().any(|p| p.borrows)),
+        &call_lifetime,
+    );
+    let reply_generics = generics(methods.iter().any(|m| m.reply_borrows), &reply_lifetime);
+    let error_generics = generics(methods.iter().any(|m| m.error_borrows), &reply_lifetime);
+    let serde = quote!(#crate_path::__private::serde);
+    let serde_crate = serde.to_string();
+    let empty_parameters = quote!(#crate_path::connection::empty_parameters).to_string();
+
+    let call_variants = methods.iter().map(|m| {
+        let variant = &m.variant;
+        let name = &m.name;
+        if m.params.is_empty() {
+            // Callers may send empty parameters, which `serde` doesn't accept for unit variants.
+            return quote! {
+                #[serde(rename = #name, deserialize_with = #empty_parameters)]
+                #variant
+            };
+        }
+        let fields = m.params.iter().map(|param| {
+            let ident = &param.ident;
+            let ty = &param.ty;
+            if param.borrows {
+                quote!(#[serde(borrow)] #ident: #ty)
+            } else {
+                quote!(#ident: #ty)
+            }
+        });
+
+        quote! {
+            #[serde(rename = #name)]
+            #variant { #(#fields),* }
+        }
+    });
+    let reply_variants = methods.iter().filter_map(|m| {
+        let variant = &m.variant;
+        m.reply.as_ref().map(|ty| quote!(#variant(#ty)))
+    });
+    let error_variants = methods.iter().filter_map(|m| {
+        let variant = &m.variant;
+        m.error.as_ref().map(|ty| quote!(#variant(#ty)))
+    });
+    let stream_methods: Vec<_> = methods
+        .iter()
+        .filter_map(|m| m.stream.as_ref().map(|ty| (&m.variant, ty)))
+        .collect();
+    let futures_util = quote!(#crate_path::__private::futures_util);
+    let (stream_items, stream_type, stream_params_type) = if stream_methods.is_empty() {
+        (
+            None,
+            quote!(#futures_util::stream::Empty<#crate_path::connection::Reply<()>>),
+            quote!(()),
+        )
+    } else {
+        let stream_variants = stream_methods
+            .iter()
+            .map(|(variant, ty)| quote!(#variant(#ty)));
+        let params_variants = stream_methods
+            .iter()
+            .map(|(variant, ty)| quote!(#variant(<#ty as #futures_util::Stream>::Item)));
+        let poll_arms = stream_methods.iter().map(|(variant, _)| {
+            quote! {
+                Self::#variant(stream) => #futures_util::StreamExt::poll_next_unpin(stream, cx)
+                    .map(|reply| reply.map(|reply| {
+                        #crate_path::connection::Reply::new(
+                            Some(#stream_params_enum::#variant(reply)),
+                            None,
+                        )
+                    }))
+            }
+        });
+        let items = quote! {
+            #[allow(
+                missing_docs,
+                missing_debug_implementations,
+                unreachable_pub,
+                private_interfaces
+            )]
+            pub enum #stream_enum {
+                #(#stream_variants),*
+            }
+
+            impl #futures_util::Stream for #stream_enum {
+                type Item = #crate_path::connection::Reply<#stream_params_enum>;
+
+                fn poll_next(
+                    self: ::core::pin::Pin<&mut Self>,
+                    cx: &mut ::core::task::Context<'_>,
+                ) -> ::core::task::Poll<Option<Self::Item>> {
+                    match self.get_mut() {
+                        #(#poll_arms,)*
+                    }
+                }
+            }
+
+            #[derive(Debug, #serde::Serialize)]
+            #[serde(crate = #serde_crate, untagged)]
+            #[allow(missing_docs, unreachable_pub, private_interfaces)]
+            pub enum #stream_params_enum {
+                #(#params_variants),*
+            }
+        };
+
+        (
+            Some(items),
+            quote!(#stream_enum),
+            quote!(#stream_params_enum),
+        )
+    };
+    let credentials = format_ident!("__zlink_credentials");
+    let fds = format_ident!("__zlink_fds");
+    let match_arms = methods.iter().map(|m| {
+        let variant = &m.variant;
+        let ident = &m.ident;
+        let params: Vec<_> = m.params.iter().map(|param| &param.ident).collect();
+        let pattern = if params.is_empty() {
+            quote!(#call_enum::#variant)
+        } else {
+            quote!(#call_enum::#variant { #(#params),* })
+        };
+        let args = m.args.iter().map(|arg| match arg {
+            Arg::Param(ident) => quote!(#ident),
+            Arg::Credentials => quote!(#credentials.as_deref()),
+            Arg::Fds => quote!(#fds),
+        });
+        let args: Vec<_> = args.collect();
+        let (reply_pattern, reply) = match (&m.reply, &m.stream) {
+            (Some(_), _) => (
+                quote!(reply),
+                quote!(#crate_path::server::MethodReply::Single(Some(#reply_enum::#variant(reply)))),
+            ),
+            (None, Some(_)) => (
+                quote!(stream),
+                quote!(#crate_path::server::MethodReply::Multi(#stream_enum::#variant(stream))),
+            ),
+            (None, None) => (
+                quote!(_),
+                quote!(#crate_path::server::MethodReply::Single(None)),
+            ),
+        };
+        let body = match &m.error {
+            Some(_) => quote! {
+                match self.#ident(#(#args),*).await {
+                    Ok(#reply_pattern) => #reply,
+                    Err(e) => #crate_path::server::MethodReply::Error(#error_enum::#variant(e)),
+                }
+            },
+            None => quote! {{
+                let #reply_pattern = self.#ident(#(#args),*).await;
+                #reply
+            }},
+        };
+
+        quote!(#pattern => #body)
+    });
+
+    // The call is consumed to get the method, so take the credentials and file descriptors out of
+    // it first.
+    let uses = |arg: fn(&Arg) -> bool| methods.iter().any(|m| m.args.iter().any(arg));
+    let uses_credentials = uses(|a| matches!(a, Arg::Credentials));
+    let uses_fds = uses(|a| matches!(a, Arg::Fds));
+    let mut_call = (uses_credentials || uses_fds).then(|| quote!(let mut call = call;));
+    let take_credentials =
+        uses_credentials.then(|| quote!(let #credentials = call.take_peer_credentials();));
+    let take_fds = uses_fds.then(|| quote!(let #fds = call.take_fds();));
+
+    Ok(quote! {
+        #item_impl
+
+        #[derive(Debug, #serde::Deserialize)]
+        #[serde(crate = #serde_crate, tag = "method", content = "parameters")]
+        #[allow(missing_docs, unreachable_pub, private_interfaces)]
+        pub enum #call_enum #call_generics {
+            #(#call_variants),*
+        }
+
+        #[derive(Debug, #serde::Serialize)]
+        #[serde(crate = #serde_crate, untagged)]
+        #[allow(missing_docs, unreachable_pub, private_interfaces)]
+        pub enum #reply_enum #reply_generics {
+            #(#reply_variants),*
+        }
+
+        #[derive(Debug, #serde::Serialize)]
+        #[serde(crate = #serde_crate, untagged)]
+        #[allow(missing_docs, unreachable_pub, private_interfaces)]
+        pub enum #error_enum #error_generics {
+            #(#error_variants),*
+        }
+
+        #stream_items
+
+        impl #crate_path::server::Service for #self_ty {
+            type MethodCall<#call_lifetime> = #call_enum #call_generics;
+            type Reply<#reply_lifetime> = #reply_enum #reply_generics
+            where
+                Self: #reply_lifetime;
+            type ReplyStream = #stream_type;
+            type ReplyStreamParams = #stream_params_type;
+            type ReplyError<#reply_lifetime> = #error_enum #error_generics
+            where
+                Self: #reply_lifetime;
+
+            async fn handle<#reply_lifetime>(
+                &#reply_lifetime mut self,
+                call: #crate_path::connection::Call<Self::MethodCall<'_>>,
+            ) -> #crate_path::server::MethodReply<
+                Self::Reply<#reply_lifetime>,
+                Self::ReplyStream,
+                Self::ReplyError<#reply_lifetime>,
+            > {
+                #mut_call
+                #take_credentials
+                #take_fds
+                match call.into_method() {
+                    #(#match_arms,)*
+                }
+            }
+        }
+    })
+}
+
+// A method of the service.
+struct Method {
+    // The Rust method name.
+    ident: Ident,
+    // The name of the enum variants for this method.
+    variant: Ident,
+    // The fully-qualified Varlink method name.
+    name: String,
+    params: Vec<Param>,
+    // The arguments of the Rust method, in order.
+    args: Vec<Arg>,
+    // The reply type, unless it's `()` or the method returns a stream.
+    reply: Option<Type>,
+    // The type of the stream of replies, for methods with the `more` attribute.
+    stream: Option<Type>,
+    reply_borrows: bool,
+    // The error type, if the method returns a `Result`.
+    error: Option<Type>,
+    error_borrows: bool,
+}
+
+impl Method {
+    // Parses the method, removing any `zlink` attributes from it in the process.
+    //
+    // Returns `None` if the function is not a method of the service.
+    fn parse(f: &mut ImplItemFn, default_interface: Option<&str>) -> Result<Option<Self>> {
+        let attrs = MethodAttrs::parse(&mut f.attrs)?;
+        let sig = &mut f.sig;
+        if sig.asyncness.is_none() || sig.receiver().is_none() {
+            return Ok(None);
+        }
+
+        let interface = match attrs.interface.as_deref().or(default_interface) {
+            Some(interface) => interface,
+            None => {
+                return Err(Error::new_spanned(
+                    &sig.ident,
+                    "no interface name given for the method, use the `interface` attribute",
+                ))
+            }
+        };
+        let method_name = attrs
+            .rename
+            .unwrap_or_else(|| to_pascal_case(&sig.ident.to_string()));
+
+        let mut params = Vec::new();
+        let mut args = Vec::new();
+        for input in sig.inputs.iter_mut().skip(1) {
+            let FnArg::Typed(arg) = input else {
+                continue;
+            };
+            let arg_attrs = ArgAttrs::parse(&mut arg.attrs)?;
+            if arg_attrs.credentials && arg_attrs.fds {
+                return Err(Error::new_spanned(
+                    &arg.pat,
+                    "an argument can't receive both the credentials and the file descriptors",
+                ));
+            } else if arg_attrs.credentials {
+                args.push(Arg::Credentials);
+                continue;
+            } else if arg_attrs.fds {
+                args.push(Arg::Fds);
+                continue;
+            }
+            let Pat::Ident(pat) = &*arg.pat else {
+                return Err(Error::new_spanned(
+                    &arg.pat,
+                    "only simple identifiers are supported as method arguments",
+                ));
+            };
+            let mut ty = (*arg.ty).clone();
+            let mut lifetimes = ElidedLifetimes::new("'m");
+            lifetimes.visit_type_mut(&mut ty);
+            args.push(Arg::Param(pat.ident.clone()));
+            params.push(Param {
+                ident: pat.ident.clone(),
+                ty,
+                borrows: lifetimes.replaced,
+            });
+        }
+
+        let (reply, error) = match &sig.output {
+            ReturnType::Default => (None, None),
+            ReturnType::Type(_, ty) => match result_types(ty)? {
+                Some((reply, error)) => (Some(reply), Some(error)),
+                None => (Some((**ty).clone()), None),
+            },
+        };
+        let (reply, stream) = match reply {
+            Some(mut ty) if attrs.more => {
+                // The stream is kept around after the method returns.
+                let mut lifetimes = ElidedLifetimes::new("'static");
+                lifetimes.visit_type_mut(&mut ty);
+                if lifetimes.replaced {
+                    return Err(Error::new_spanned(
+                        &sig.output,
+                        "the stream returned by a `more` method can't borrow",
+                    ));
+                }
+
+                (None, Some(ty))
+            }
+            None if attrs.more => {
+                return Err(Error::new_spanned(
+                    &sig.ident,
+                    "a `more` method must return a stream of replies",
+                ))
+            }
+            reply => (reply, None),
+        };
+        let mut reply_lifetime = ElidedLifetimes::new("'ser");
+        let reply = reply.filter(|ty| !is_unit(ty)).map(|mut ty| {
+            reply_lifetime.visit_type_mut(&mut ty);
+            ty
+        });
+        let mut error_lifetime = ElidedLifetimes::new("'ser");
+        let error = error.map(|mut ty| {
+            error_lifetime.visit_type_mut(&mut ty);
+            ty
+        });
+
+        Ok(Some(Self {
+            ident: sig.ident.clone(),
+            variant: format_ident!("{}", to_pascal_case(&sig.ident.to_string())),
+            name: format!("{interface}.{method_name}"),
+            params,
+            args,
+            reply,
+            stream,
+            reply_borrows: reply_lifetime.replaced,
+            error,
+            error_borrows: error_lifetime.replaced,
+        }))
+    }
+}
+
+// A parameter of a method.
+struct Param {
+    ident: Ident,
+    // The type, with elided lifetimes replaced.
+    ty: Type,
+    // If the type borrows from the method call.
+    borrows: bool,
+}
+
+// An argument of a method.
+enum Arg {
+    // A parameter of the method call.
+    Param(Ident),
+    // The credentials of the caller.
+    Credentials,
+    // The file descriptors passed along with the method call.
+    Fds,
+}
+
+// The `zlink` attributes of a method argument.
+#[derive(Default)]
+struct ArgAttrs {
+    credentials: bool,
+    fds: bool,
+}
+
+impl ArgAttrs {
+    // Parses the `zlink` attributes and removes them from `attrs`.
+    fn parse(attrs: &mut Vec<Attribute>) -> Result<Self> {
+        let mut arg_attrs = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("zlink")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("credentials") {
+                    arg_attrs.credentials = true;
+                } else if meta.path.is_ident("fds") {
+                    arg_attrs.fds = true;
+                } else {
+                    return Err(meta.error("unsupported `zlink` attribute"));
+                }
+
+                Ok(())
+            })?;
+        }
+        attrs.retain(|a| !a.path().is_ident("zlink"));
+
+        Ok(arg_attrs)
+    }
+}
+
+// The `zlink` attributes of a method.
+#[derive(Default)]
+struct MethodAttrs {
+    interface: Option<String>,
+    rename: Option<String>,
+    more: bool,
+}
+
+impl MethodAttrs {
+    // Parses the `zlink` attributes and removes them from `attrs`.
+    fn parse(attrs: &mut Vec<Attribute>) -> Result<Self> {
+        let mut method_attrs = Self::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("zlink")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("interface") {
+                    method_attrs.interface = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("rename") {
+                    method_attrs.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("more") {
+                    method_attrs.more = true;
+                } else {
+                    return Err(meta.error("unsupported `zlink` attribute"));
+                }
+
+                Ok(())
+            })?;
+        }
+        attrs.retain(|a| !a.path().is_ident("zlink"));
+
+        Ok(method_attrs)
+    }
+}
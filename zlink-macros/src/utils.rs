@@ -0,0 +1,94 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    visit_mut::VisitMut, Error, GenericArgument, Lifetime, PathArguments, Result, Type,
+    TypeReference,
+};
+
+// Replaces all elided lifetimes in a type with a named lifetime.
+pub(crate) struct ElidedLifetimes {
+    lifetime: Lifetime,
+    pub(crate) replaced: bool,
+}
+
+impl ElidedLifetimes {
+    pub(crate) fn new(lifetime: &str) -> Self {
+        Self {
+            lifetime: Lifetime::new(lifetime, Span::call_site()),
+            replaced: false,
+        }
+    }
+}
+
+impl VisitMut for ElidedLifetimes {
+    fn visit_type_reference_mut(&mut self, reference: &mut TypeReference) {
+        if reference.lifetime.is_none() {
+            reference.lifetime = Some(self.lifetime.clone());
+            self.replaced = true;
+        }
+        syn::visit_mut::visit_type_reference_mut(self, reference);
+    }
+
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if lifetime.ident == "_" {
+            *lifetime = self.lifetime.clone();
+            self.replaced = true;
+        }
+    }
+}
+
+// If `ty` is a `Result`, returns its success and error types.
+pub(crate) fn result_types(ty: &Type) -> Result<Option<(Type, Type)>> {
+    let Type::Path(path) = ty else {
+        return Ok(None);
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return Ok(None);
+    };
+    if segment.ident != "Result" {
+        return Ok(None);
+    }
+
+    let args: Vec<_> = match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    };
+    match <[Type; 2]>::try_from(args) {
+        Ok([reply, error]) => Ok(Some((reply, error))),
+        Err(_) => Err(Error::new_spanned(
+            ty,
+            "`Result` return type must specify both success and error types",
+        )),
+    }
+}
+
+pub(crate) fn is_unit(ty: &Type) -> bool {
+    matches!(ty, Type::Tuple(tuple) if tuple.elems.is_empty())
+}
+
+pub(crate) fn generics(borrows: bool, lifetime: &Lifetime) -> TokenStream {
+    if borrows {
+        quote!(<#lifetime>)
+    } else {
+        quote!()
+    }
+}
+
+pub(crate) fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+use std::sync::Arc;
+
+use serde::Serialize;
+use zlink::{
+    connection::{Call, Credentials},
+    server::{MethodReply, Service},
+};
+
+#[tokio::test]
+async fn credentials() {
+    let mut guard = Guard;
+
+    let mut call: Call<<Guard as Service>::MethodCall<'_>> = serde_json::from_str(
+        r#"{"method":"org.example.guard.Enter","parameters":{"door":"main"}}"#,
+    )
+    .unwrap();
+    call.set_peer_credentials(Some(Arc::new(Credentials::new(1000, 1000, None, None))));
+    let MethodReply::Single(Some(reply)) = guard.handle(call).await else {
+        panic!("unexpected reply");
+    };
+    assert_eq!(
+        serde_json::to_string(&reply).unwrap(),
+        r#"{"door":"main","uid":1000}"#
+    );
+
+    // Without credentials, e.g if the socket doesn't support them.
+    let call: Call<<Guard as Service>::MethodCall<'_>> = serde_json::from_str(
+        r#"{"method":"org.example.guard.Enter","parameters":{"door":"back"}}"#,
+    )
+    .unwrap();
+    let MethodReply::Single(Some(reply)) = guard.handle(call).await else {
+        panic!("unexpected reply");
+    };
+    assert_eq!(
+        serde_json::to_string(&reply).unwrap(),
+        r#"{"door":"back","uid":null}"#
+    );
+}
+
+struct Guard;
+
+#[zlink::service(interface = "org.example.guard")]
+impl Guard {
+    async fn enter(
+        &mut self,
+        #[zlink(credentials)] credentials: Option<&Credentials>,
+        door: String,
+    ) -> Entry {
+        Entry {
+            door,
+            uid: credentials.map(Credentials::uid),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Entry {
+    door: String,
+    uid: Option<u32>,
+}
@@ -0,0 +1,57 @@
+#![cfg(unix)]
+
+use std::{
+    fs::File,
+    os::fd::{AsRawFd, OwnedFd},
+};
+
+use serde::Serialize;
+use zlink::{
+    connection::{Call, FdIndex},
+    server::{MethodReply, Service},
+};
+
+#[tokio::test]
+async fn fds() {
+    let mut archive = Archive;
+
+    let mut call: Call<<Archive as Service>::MethodCall<'_>> = serde_json::from_str(
+        r#"{"method":"org.example.archive.Store","parameters":{"name":"log","file":1}}"#,
+    )
+    .unwrap();
+    let fds: Vec<OwnedFd> = (0..2)
+        .map(|_| File::open("/dev/null").unwrap().into())
+        .collect();
+    let fd = fds[1].as_raw_fd();
+    call.set_fds(fds);
+    let MethodReply::Single(Some(reply)) = archive.handle(call).await else {
+        panic!("unexpected reply");
+    };
+    assert_eq!(
+        serde_json::to_string(&reply).unwrap(),
+        format!(r#"{{"name":"log","fd":{fd}}}"#)
+    );
+}
+
+struct Archive;
+
+#[zlink::service(interface = "org.example.archive")]
+impl Archive {
+    async fn store(
+        &mut self,
+        name: String,
+        file: FdIndex,
+        #[zlink(fds)] fds: Vec<OwnedFd>,
+    ) -> Stored {
+        Stored {
+            name,
+            fd: file.fd(&fds).map(AsRawFd::as_raw_fd),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Stored {
+    name: String,
+    fd: Option<i32>,
+}
@@ -0,0 +1,173 @@
+use std::{cell::RefCell, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+use zlink::{
+    connection::{Call, Channel, Reply, Socket},
+    server::{serve_next, MethodReply, Service},
+    varlink_service::Info,
+    Connection,
+};
+
+#[tokio::test]
+async fn proxy() {
+    let written = Rc::new(RefCell::new(Vec::new()));
+    let socket = TestSocket {
+        replies: [
+            r#"{"parameters":{"tylium_level":100}}"#,
+            r#"{"error":"org.example.ftl.UnknownDestination","parameters":{"destination":"Kobol"}}"#,
+            r#"{"parameters":{"tylium_level":99},"continues":true}"#,
+            r#"{"parameters":{"tylium_level":98}}"#,
+        ]
+        .iter()
+        .flat_map(|r| r.bytes().chain(Some(b'\0')))
+        .collect(),
+        written: written.clone(),
+    };
+    let mut proxy = FtlProxy::new(Connection::new(socket));
+
+    let condition = proxy.get_drive_condition().await.unwrap();
+    assert_eq!(condition.tylium_level, 100);
+
+    match proxy.jump("Kobol").await {
+        Err(zlink::Error::Reply(FtlError::UnknownDestination { destination })) => {
+            assert_eq!(destination, "Kobol")
+        }
+        r => panic!("unexpected reply: {r:?}"),
+    }
+
+    let mut stream = proxy.monitor_drive_condition().await.unwrap();
+    let mut levels = Vec::new();
+    while let Some(condition) = stream.next().await {
+        levels.push(condition.unwrap().tylium_level);
+    }
+    assert_eq!(levels, [99, 98]);
+
+    let written = written.borrow();
+    let calls: Vec<_> = written
+        .split(|b| *b == b'\0')
+        .filter(|c| !c.is_empty())
+        .map(|c| std::str::from_utf8(c).unwrap())
+        .collect();
+    assert_eq!(
+        calls,
+        [
+            r#"{"method":"org.example.ftl.GetDriveCondition"}"#,
+            r#"{"method":"org.example.ftl.Jump","parameters":{"destination":"Kobol"}}"#,
+            r#"{"method":"org.example.ftl.Monitor","more":true}"#,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn stream_from_service() {
+    let channel = Channel::new();
+    let (client, server) = channel.split();
+    let mut proxy = FtlProxy::new(Connection::new(client));
+    let mut server = Connection::new(server);
+    let mut monitor = Monitor {
+        levels: vec![99, 98],
+    };
+    let info = Info::default();
+
+    let serve = serve_next::<_, _, FtlError>(&mut monitor, &mut server, &info);
+    let receive = async {
+        let mut stream = proxy.monitor_drive_condition().await.unwrap();
+        let mut levels = Vec::new();
+        while let Some(condition) = stream.next().await {
+            levels.push(condition.unwrap().tylium_level);
+        }
+
+        levels
+    };
+    let (served, levels) = tokio::join!(serve, receive);
+    served.unwrap();
+    // The server marks the last reply as such, so there's no trailing empty one.
+    assert_eq!(levels, [99, 98]);
+}
+
+#[zlink::proxy("org.example.ftl")]
+trait Ftl {
+    async fn get_drive_condition(&mut self) -> zlink::Result<DriveCondition, FtlError>;
+    async fn jump(&mut self, destination: &str) -> zlink::Result<(), FtlError>;
+    #[zlink(rename = "Monitor", more)]
+    async fn monitor_drive_condition(&mut self) -> zlink::Result<DriveCondition, FtlError>;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DriveCondition {
+    tylium_level: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "error", content = "parameters")]
+enum FtlError {
+    #[serde(rename = "org.example.ftl.UnknownDestination")]
+    UnknownDestination { destination: String },
+}
+
+// A service sending the drive condition for each of the `levels` when monitored.
+#[derive(Debug)]
+struct Monitor {
+    levels: Vec<i64>,
+}
+
+impl Service for Monitor {
+    type MethodCall<'de> = MonitorCall;
+    type Reply<'ser>
+        = DriveCondition
+    where
+        Self: 'ser;
+    type ReplyStream = futures_util::stream::Iter<std::vec::IntoIter<Reply<DriveCondition>>>;
+    type ReplyStreamParams = DriveCondition;
+    type ReplyError<'ser>
+        = FtlError
+    where
+        Self: 'ser;
+
+    async fn handle<'ser>(
+        &'ser mut self,
+        _call: Call<MonitorCall>,
+    ) -> MethodReply<DriveCondition, Self::ReplyStream, FtlError> {
+        // The server takes care of the `continues` flags.
+        let replies: Vec<_> = self
+            .levels
+            .iter()
+            .map(|&tylium_level| Reply::new(Some(DriveCondition { tylium_level }), None))
+            .collect();
+
+        MethodReply::Multi(futures_util::stream::iter(replies))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", content = "parameters")]
+enum MonitorCall {
+    #[serde(
+        rename = "org.example.ftl.Monitor",
+        deserialize_with = "zlink::connection::empty_parameters"
+    )]
+    Monitor,
+}
+
+// A socket that reads pre-defined replies and keeps the written data.
+#[derive(Debug)]
+struct TestSocket {
+    replies: Vec<u8>,
+    written: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Socket for TestSocket {
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> zlink::Result<usize, ReplyError> {
+        let len = self.replies.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.replies[..len]);
+        self.replies.drain(..len);
+
+        Ok(len)
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> zlink::Result<(), ReplyError> {
+        self.written.borrow_mut().extend_from_slice(buf);
+
+        Ok(())
+    }
+}
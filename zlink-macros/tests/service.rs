@@ -0,0 +1,157 @@
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use zlink::{
+    connection::Call,
+    server::{MethodReply, Service},
+};
+
+#[tokio::test]
+async fn service() {
+    let mut ftl = Ftl {
+        drive_condition: DriveCondition {
+            state: DriveState::Idle,
+            tylium_level: 100,
+        },
+    };
+
+    let call: Call<<Ftl as Service>::MethodCall<'_>> =
+        serde_json::from_str(r#"{"method":"org.example.ftl.Monitor"}"#).unwrap();
+    let MethodReply::Single(Some(reply)) = ftl.handle(call).await else {
+        panic!("unexpected reply");
+    };
+    assert_eq!(
+        serde_json::to_string(&reply).unwrap(),
+        r#"{"state":"idle","tylium_level":100}"#
+    );
+
+    let call: Call<<Ftl as Service>::MethodCall<'_>> = serde_json::from_str(
+        r#"{"method":"org.example.ftl.Jump","parameters":{"destination":"Caprica","speed":9}}"#,
+    )
+    .unwrap();
+    let MethodReply::Single(None) = ftl.handle(call).await else {
+        panic!("unexpected reply");
+    };
+    assert_eq!(ftl.drive_condition.state, DriveState::Busy);
+
+    let call: Call<<Ftl as Service>::MethodCall<'_>> = serde_json::from_str(
+        r#"{"method":"org.example.ftl.Jump","parameters":{"destination":"Kobol","speed":9}}"#,
+    )
+    .unwrap();
+    let MethodReply::Error(error) = ftl.handle(call).await else {
+        panic!("unexpected reply");
+    };
+    assert_eq!(
+        serde_json::to_string(&error).unwrap(),
+        r#"{"error":"org.example.ftl.UnknownDestination","parameters":{"destination":"Kobol"}}"#
+    );
+
+    // Methods without parameters also accept empty parameters.
+    let call: Call<<Ftl as Service>::MethodCall<'_>> =
+        serde_json::from_str(r#"{"method":"org.example.ftl.GetTyliumLevel","parameters":{}}"#)
+            .unwrap();
+    let MethodReply::Single(Some(reply)) = ftl.handle(call).await else {
+        panic!("unexpected reply");
+    };
+    assert_eq!(serde_json::to_string(&reply).unwrap(), r#"{"level":91}"#);
+
+    // Methods called with `more` return a stream of replies.
+    let call: Call<<Ftl as Service>::MethodCall<'_>> = serde_json::from_str(
+        r#"{"method":"org.example.ftl.ForecastTyliumLevel","parameters":{"jumps":2},"more":true}"#,
+    )
+    .unwrap();
+    let MethodReply::Multi(replies) = ftl.handle(call).await else {
+        panic!("unexpected reply");
+    };
+    let replies: Vec<_> = replies
+        .map(|reply| serde_json::to_string(reply.parameters().unwrap()).unwrap())
+        .collect()
+        .await;
+    assert_eq!(replies, [r#"{"level":82}"#, r#"{"level":73}"#]);
+
+    let call: Call<<Ftl as Service>::MethodCall<'_>> = serde_json::from_str(
+        r#"{"method":"org.example.ftl.ForecastTyliumLevel","parameters":{"jumps":20},"more":true}"#,
+    )
+    .unwrap();
+    let MethodReply::Error(error) = ftl.handle(call).await else {
+        panic!("unexpected reply");
+    };
+    assert_eq!(
+        serde_json::to_string(&error).unwrap(),
+        r#"{"error":"org.example.ftl.NotEnoughTylium"}"#
+    );
+}
+
+struct Ftl {
+    drive_condition: DriveCondition,
+}
+
+#[zlink::service(interface = "org.example.ftl")]
+impl Ftl {
+    async fn monitor(&mut self) -> &DriveCondition {
+        &self.drive_condition
+    }
+
+    async fn jump(&mut self, destination: &str, speed: i64) -> Result<(), FtlError> {
+        if destination != "Caprica" {
+            return Err(FtlError::UnknownDestination {
+                destination: destination.to_string(),
+            });
+        }
+        self.drive_condition.state = DriveState::Busy;
+        self.drive_condition.tylium_level -= speed;
+
+        Ok(())
+    }
+
+    #[zlink(rename = "GetTyliumLevel")]
+    async fn tylium_level(&self) -> TyliumLevel {
+        TyliumLevel {
+            level: self.drive_condition.tylium_level,
+        }
+    }
+
+    #[zlink(more)]
+    async fn forecast_tylium_level(
+        &self,
+        jumps: i64,
+    ) -> Result<stream::Iter<std::vec::IntoIter<TyliumLevel>>, FtlError> {
+        let level = self.drive_condition.tylium_level;
+        if jumps * 9 > level {
+            return Err(FtlError::NotEnoughTylium);
+        }
+        let levels: Vec<_> = (1..=jumps)
+            .map(|jump| TyliumLevel {
+                level: level - jump * 9,
+            })
+            .collect();
+
+        Ok(stream::iter(levels))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DriveCondition {
+    state: DriveState,
+    tylium_level: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum DriveState {
+    Idle,
+    Busy,
+}
+
+#[derive(Debug, Serialize)]
+struct TyliumLevel {
+    level: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "error", content = "parameters")]
+enum FtlError {
+    #[serde(rename = "org.example.ftl.UnknownDestination")]
+    UnknownDestination { destination: String },
+    #[serde(rename = "org.example.ftl.NotEnoughTylium")]
+    NotEnoughTylium,
+}
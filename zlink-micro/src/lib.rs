@@ -0,0 +1,13 @@
+#![cfg_attr(not(test), no_std)]
+#![deny(
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_idioms,
+    missing_docs
+)]
+#![warn(unreachable_pub)]
+#![doc = include_str!("../../README.md")]
+
+pub use zlink::mux::{Multiplexer, Stream};
+pub use zlink::*;
+pub mod usb;
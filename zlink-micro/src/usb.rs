@@ -0,0 +1,259 @@
+//! The USB transport, based on `embassy-usb`.
+//!
+//! The device exposes a vendor-specific interface with a pair of bulk endpoints. Use [`endpoints`]
+//! to add it to the device, then pass the writer to [`Multiplexer::new`] and the reader to
+//! [`Multiplexer::run`].
+//!
+//! [`Multiplexer::new`]: crate::Multiplexer::new
+//! [`Multiplexer::run`]: crate::Multiplexer::run
+
+use embassy_usb::{
+    driver::{Driver, Endpoint, EndpointIn, EndpointOut},
+    Builder,
+};
+use zlink::{connection::Socket, Result};
+
+/// The maximum packet size of the bulk endpoints.
+pub const MAX_PACKET_SIZE: u16 = 64;
+
+// Vendor-specific class.
+const CLASS: u8 = 0xff;
+
+/// Add the zlink interface to the USB device being built.
+///
+/// Returns the reading and writing ends of the bulk endpoints pair.
+pub fn endpoints<'d, D>(
+    builder: &mut Builder<'d, D>,
+) -> (UsbReader<D::EndpointOut>, UsbWriter<D::EndpointIn>)
+where
+    D: Driver<'d>,
+{
+    let mut function = builder.function(CLASS, 0, 0);
+    let mut interface = function.interface();
+    let mut alt = interface.alt_setting(CLASS, 0, 0, None);
+    let out = alt.endpoint_bulk_out(MAX_PACKET_SIZE);
+    let r#in = alt.endpoint_bulk_in(MAX_PACKET_SIZE);
+
+    (UsbReader::new(out), UsbWriter::new(r#in))
+}
+
+/// The reading end of the USB transport, to be passed to [`Multiplexer::run`].
+///
+/// Writing to it always fails.
+///
+/// [`Multiplexer::run`]: crate::Multiplexer::run
+#[derive(Debug)]
+pub struct UsbReader<E> {
+    endpoint: E,
+    packet: [u8; MAX_PACKET_SIZE as usize],
+    pos: usize,
+    len: usize,
+}
+
+impl<E> UsbReader<E>
+where
+    E: EndpointOut,
+{
+    /// Create a new reader from a bulk OUT endpoint.
+    pub fn new(endpoint: E) -> Self {
+        Self {
+            endpoint,
+            packet: [0; MAX_PACKET_SIZE as usize],
+            pos: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<E> Socket for UsbReader<E>
+where
+    E: EndpointOut,
+{
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> Result<usize, ReplyError> {
+        while self.pos == self.len {
+            self.endpoint.wait_enabled().await;
+            // Zero-length packets only mark the end of transfers, so we skip them.
+            self.len = self
+                .endpoint
+                .read(&mut self.packet)
+                .await
+                .map_err(|_| zlink::Error::SocketRead)?;
+            self.pos = 0;
+        }
+
+        let n = (self.len - self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.packet[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+
+    async fn write<ReplyError>(&mut self, _buf: &[u8]) -> Result<(), ReplyError> {
+        Err(zlink::Error::SocketWrite)
+    }
+}
+
+/// The writing end of the USB transport, to be passed to [`Multiplexer::new`].
+///
+/// Reading from it always fails.
+///
+/// [`Multiplexer::new`]: crate::Multiplexer::new
+#[derive(Debug)]
+pub struct UsbWriter<E> {
+    endpoint: E,
+}
+
+impl<E> UsbWriter<E>
+where
+    E: EndpointIn,
+{
+    /// Create a new writer from a bulk IN endpoint.
+    pub fn new(endpoint: E) -> Self {
+        Self { endpoint }
+    }
+}
+
+impl<E> Socket for UsbWriter<E>
+where
+    E: EndpointIn,
+{
+    async fn read<ReplyError>(&mut self, _buf: &mut [u8]) -> Result<usize, ReplyError> {
+        Err(zlink::Error::SocketRead)
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> Result<(), ReplyError> {
+        self.endpoint.wait_enabled().await;
+        let max_packet_size = self.endpoint.info().max_packet_size as usize;
+        for packet in buf.chunks(max_packet_size) {
+            self.endpoint
+                .write(packet)
+                .await
+                .map_err(|_| zlink::Error::SocketWrite)?;
+        }
+        // The host can't tell where the transfer ends if the last packet is full.
+        if !buf.is_empty() && buf.len() % max_packet_size == 0 {
+            self.endpoint
+                .write(&[])
+                .await
+                .map_err(|_| zlink::Error::SocketWrite)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use embassy_usb::driver::{
+        Direction, EndpointAddress, EndpointError, EndpointInfo, EndpointType,
+    };
+    use futures_executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn reader() {
+        let packets = [vec![1; PACKET_SIZE], vec![], vec![2; 3]];
+        let mut reader = UsbReader::new(MockEndpoint::new(Direction::Out, packets));
+
+        block_on(async {
+            // Reads can span packets, while zero-length packets are skipped.
+            let mut buf = [0; PACKET_SIZE - 2];
+            assert_eq!(
+                reader.read::<&'static str>(&mut buf).await.unwrap(),
+                buf.len()
+            );
+            assert_eq!(reader.read::<&'static str>(&mut buf).await.unwrap(), 2);
+            assert_eq!(reader.read::<&'static str>(&mut buf).await.unwrap(), 3);
+            assert_eq!(buf[..3], [2; 3]);
+
+            // The endpoint failing.
+            assert!(matches!(
+                reader.read::<&'static str>(&mut buf).await,
+                Err(zlink::Error::SocketRead)
+            ));
+            assert!(reader.write::<&'static str>(b"hello").await.is_err());
+        });
+    }
+
+    #[test]
+    fn writer() {
+        let mut writer = UsbWriter::new(MockEndpoint::new(Direction::In, []));
+
+        block_on(async {
+            writer
+                .write::<&'static str>(&[0; PACKET_SIZE + 1])
+                .await
+                .unwrap();
+            // A zero-length packet follows transfers that are a multiple of the packet size.
+            writer
+                .write::<&'static str>(&[0; 2 * PACKET_SIZE])
+                .await
+                .unwrap();
+            writer.write::<&'static str>(&[]).await.unwrap();
+            let sizes: Vec<_> = writer.endpoint.packets.iter().map(Vec::len).collect();
+            assert_eq!(
+                sizes,
+                [PACKET_SIZE, 1, PACKET_SIZE, PACKET_SIZE, 0],
+                "unexpected packets"
+            );
+
+            let mut buf = [0; 1];
+            assert!(writer.read::<&'static str>(&mut buf).await.is_err());
+        });
+    }
+
+    const PACKET_SIZE: usize = 8;
+
+    // An endpoint reading the given packets or recording the written ones.
+    #[derive(Debug)]
+    struct MockEndpoint {
+        info: EndpointInfo,
+        packets: VecDeque<Vec<u8>>,
+    }
+
+    impl MockEndpoint {
+        fn new(direction: Direction, packets: impl IntoIterator<Item = Vec<u8>>) -> Self {
+            Self {
+                info: EndpointInfo {
+                    addr: EndpointAddress::from_parts(1, direction),
+                    ep_type: EndpointType::Bulk,
+                    max_packet_size: PACKET_SIZE as u16,
+                    interval_ms: 0,
+                },
+                packets: packets.into_iter().collect(),
+            }
+        }
+    }
+
+    impl Endpoint for MockEndpoint {
+        fn info(&self) -> &EndpointInfo {
+            &self.info
+        }
+
+        async fn wait_enabled(&mut self) {}
+    }
+
+    impl EndpointOut for MockEndpoint {
+        async fn read(&mut self, buf: &mut [u8]) -> core::result::Result<usize, EndpointError> {
+            let packet = self.packets.pop_front().ok_or(EndpointError::Disabled)?;
+            if packet.len() > buf.len() {
+                return Err(EndpointError::BufferOverflow);
+            }
+            buf[..packet.len()].copy_from_slice(&packet);
+
+            Ok(packet.len())
+        }
+    }
+
+    impl EndpointIn for MockEndpoint {
+        async fn write(&mut self, buf: &[u8]) -> core::result::Result<(), EndpointError> {
+            assert!(buf.len() <= PACKET_SIZE);
+            self.packets.push_back(buf.to_vec());
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::{fs::Permissions, os::unix::net::UnixListener, path::Path};
+
+use async_io::Async;
+use zlink::{server, unix::SocketFile, Result};
+
+use super::{Connection, Stream};
+
+/// Create a [`Listener`] bound to the Unix Domain Socket at the given path.
+///
+/// If a socket file already exists at `path` but no one is listening on it anymore, the file is
+/// removed first. The socket file is removed again when the listener is dropped.
+pub fn bind<P>(path: P) -> Result<Listener, &'static str>
+where
+    P: AsRef<Path>,
+{
+    let (listener, file) =
+        SocketFile::bind(path.as_ref(), |path| Async::<UnixListener>::bind(path))?;
+
+    Ok(Listener {
+        listener,
+        file: Some(file),
+    })
+}
+
+/// Create a [`Listener`] bound to the Unix Domain Socket at the given path, with the given
+/// permissions.
+///
+/// Clients need write permission on the socket to be able to connect to it. The socket file only
+/// appears at `path` once it has the given permissions, so no client can connect to it before.
+/// Otherwise, this is the same as [`bind`].
+pub fn bind_with_permissions<P>(path: P, permissions: Permissions) -> Result<Listener, &'static str>
+where
+    P: AsRef<Path>,
+{
+    let (listener, file) = SocketFile::bind_with_permissions(path.as_ref(), permissions, |path| {
+        Async::<UnixListener>::bind(path)
+    })?;
+
+    Ok(Listener {
+        listener,
+        file: Some(file),
+    })
+}
+
+/// Create a [`Listener`] bound to the abstract Unix Domain Socket with the given name.
+///
+/// The name is given without the leading `@` (or NUL byte). Unlike with [`bind`], no file is
+/// created for the socket.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn bind_abstract<N>(name: N) -> Result<Listener, &'static str>
+where
+    N: AsRef<[u8]>,
+{
+    use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+    let address = SocketAddr::from_abstract_name(name)?;
+    let listener = UnixListener::bind_addr(&address)?;
+
+    Ok(Listener {
+        listener: Async::new(listener)?,
+        file: None,
+    })
+}
+
+/// A [`server::Listener`] implementation using Unix Domain Sockets.
+///
+/// Use [`bind`] or [`bind_abstract`] to create one.
+#[derive(Debug)]
+pub struct Listener {
+    listener: Async<UnixListener>,
+    // `None` for abstract sockets.
+    file: Option<SocketFile>,
+}
+
+impl Listener {
+    /// The path of the socket, if it's not an abstract socket.
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(SocketFile::path)
+    }
+}
+
+impl server::Listener for Listener {
+    type Socket = Stream;
+
+    async fn accept<ReplyError>(&mut self) -> Result<Connection, ReplyError> {
+        let (stream, _) = self.listener.accept().await?;
+
+        Ok(Connection::new(stream.into()))
+    }
+}
@@ -0,0 +1,153 @@
+//! Provides transport over Unix Domain Sockets.
+
+mod listener;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use listener::bind_abstract;
+pub use listener::{bind, bind_with_permissions, Listener};
+
+use std::{
+    io::{self, Read, Write},
+    os::{
+        fd::{BorrowedFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+};
+
+use async_io::Async;
+use zlink::{connection::Socket, Result};
+
+/// The connection type that uses Unix Domain Sockets for transport.
+pub type Connection = zlink::Connection<Stream>;
+
+/// Connect to Unix Domain Socket at the given path.
+pub async fn connect<P>(path: P) -> Result<Connection, &'static str>
+where
+    P: AsRef<std::path::Path>,
+{
+    Async::<UnixStream>::connect(path)
+        .await
+        .map(Stream)
+        .map(Connection::new)
+        .map_err(Into::into)
+}
+
+/// Connect to the abstract Unix Domain Socket with the given name.
+///
+/// The name is given without the leading `@` (or NUL byte).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub async fn connect_abstract<N>(name: N) -> Result<Connection, &'static str>
+where
+    N: AsRef<[u8]>,
+{
+    use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+    let address = SocketAddr::from_abstract_name(name)?;
+    // Connecting to a Unix Domain Socket doesn't block for long so no need for a blocking task.
+    let stream = UnixStream::connect_addr(&address)?;
+
+    Ok(Connection::new(Stream(Async::new(stream)?)))
+}
+
+/// The [`Socket`] implementation using Unix Domain Sockets.
+///
+/// This supports passing file descriptors along with the messages.
+#[derive(Debug)]
+pub struct Stream(Async<UnixStream>);
+
+impl Socket for Stream {
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> Result<usize, ReplyError> {
+        self.0
+            .read_with(|mut stream| stream.read(buf))
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> Result<(), ReplyError> {
+        let mut pos = 0;
+
+        while pos < buf.len() {
+            let n = self
+                .0
+                .write_with(|mut stream| stream.write(&buf[pos..]))
+                .await?;
+            pos += n;
+        }
+
+        Ok(())
+    }
+
+    async fn read_with_fds<ReplyError>(
+        &mut self,
+        buf: &mut [u8],
+        fds: &mut Vec<OwnedFd>,
+    ) -> Result<usize, ReplyError> {
+        let bytes_read = self
+            .0
+            .read_with(|stream| zlink::unix::recv_with_fds(stream, &mut *buf, &mut *fds))
+            .await?;
+
+        Ok(bytes_read)
+    }
+
+    async fn write_with_fds<ReplyError>(
+        &mut self,
+        buf: &[u8],
+        fds: &[BorrowedFd<'_>],
+    ) -> Result<(), ReplyError> {
+        if fds.is_empty() {
+            return self.write(buf).await;
+        }
+
+        // The file descriptors are sent along with the first chunk of data.
+        let written = self
+            .0
+            .write_with(|stream| zlink::unix::send_with_fds(stream, buf, fds))
+            .await?;
+
+        self.write(&buf[written..]).await
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn peer_credentials(&self) -> io::Result<zlink::connection::Credentials> {
+        let credentials = rustix::net::sockopt::socket_peercred(&self.0)?;
+        let pid = credentials.pid.as_raw_nonzero().get();
+        #[cfg(target_os = "linux")]
+        let pidfd = zlink::unix::peer_pidfd(&self.0);
+        #[cfg(not(target_os = "linux"))]
+        let pidfd = None;
+
+        Ok(zlink::connection::Credentials::new(
+            credentials.uid.as_raw(),
+            credentials.gid.as_raw(),
+            Some(pid),
+            pidfd,
+        ))
+    }
+
+    #[cfg(any(
+        target_vendor = "apple",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    fn peer_credentials(&self) -> io::Result<zlink::connection::Credentials> {
+        use std::os::fd::AsRawFd;
+
+        let mut uid = 0;
+        let mut gid = 0;
+        // SAFETY: The socket is valid for the duration of the call and the IDs are written to
+        // valid locations.
+        if unsafe { libc::getpeereid(self.0.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(zlink::connection::Credentials::new(uid, gid, None, None))
+    }
+}
+
+impl From<Async<UnixStream>> for Stream {
+    fn from(stream: Async<UnixStream>) -> Self {
+        Self(stream)
+    }
+}
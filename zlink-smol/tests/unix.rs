@@ -0,0 +1,10 @@
+use zlink_smol as rt;
+
+#[path = "../../tests/ping.rs"]
+mod ping;
+#[path = "../../tests/unix.rs"]
+mod suite;
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    async_io::block_on(future)
+}
@@ -0,0 +1,185 @@
+//! Provides the `exec:` transport.
+//!
+//! The client spawns the service executable and talks to it over a socket pair. The service end of
+//! the pair is passed to the child as file descriptor 3, following the systemd socket activation
+//! protocol (`LISTEN_FDS=1`, `LISTEN_FDNAMES=varlink`), as `varlinkctl` does. This allows running
+//! a service only for the lifetime of a connection, e.g to test it hermetically.
+//!
+//! On the service side, [`connection_from_env`] detects this mode and gives access to the
+//! connection.
+
+use std::{
+    ffi::OsStr,
+    io,
+    os::fd::{AsFd, BorrowedFd, FromRawFd, OwnedFd},
+    process::Stdio,
+};
+
+use rustix::io::{dup2, fcntl_setfd, FdFlags};
+use tokio::{
+    net::UnixStream,
+    process::{Child, Command},
+};
+use zlink::{
+    connection::{Credentials, Socket},
+    Result,
+};
+
+use crate::{systemd, unix};
+
+/// The connection type that uses the `exec:` transport.
+pub type Connection = zlink::Connection<Stream>;
+
+// The name of the file descriptor passed to the service.
+const FD_NAME: &str = "varlink";
+
+/// Spawn the service executable `program` with `args` and connect to it.
+///
+/// See [`Stream::spawn`] for details.
+pub fn spawn<P, I, A>(program: P, args: I) -> Result<Connection, &'static str>
+where
+    P: AsRef<OsStr>,
+    I: IntoIterator<Item = A>,
+    A: AsRef<OsStr>,
+{
+    Stream::spawn(program, args)
+        .map(Connection::new)
+        .map_err(Into::into)
+}
+
+/// Create a [`zlink::Connection`] for the socket passed by an `exec:` client, if any.
+///
+/// This returns `None` if this process wasn't spawned by an `exec:` client. The environment is
+/// handled in the same way as [`systemd::listen_fds`] does. This must be called from the context
+/// of a tokio runtime.
+pub fn connection_from_env() -> Result<Option<zlink::Connection<crate::Stream>>, &'static str> {
+    let fds = systemd::env_fds()?;
+    if fds.len() != 1 || fds[0].1 != FD_NAME {
+        return Ok(None);
+    }
+
+    systemd::connection_from_env().map(Some)
+}
+
+/// The [`Socket`] implementation of the `exec:` transport.
+///
+/// The stream owns the spawned child process, which is killed when the stream is dropped.
+#[derive(Debug)]
+pub struct Stream {
+    stream: unix::Stream,
+    child: Child,
+}
+
+impl Stream {
+    /// Spawn the service executable `program` with `args` and connect to it.
+    ///
+    /// The child inherits the environment, standard output and standard error but not the standard
+    /// input. It's spawned through `/bin/sh`, so that `LISTEN_PID` can be set to its process ID.
+    /// This must be called from the context of a tokio runtime.
+    pub fn spawn<P, I, A>(program: P, args: I) -> io::Result<Self>
+    where
+        P: AsRef<OsStr>,
+        I: IntoIterator<Item = A>,
+        A: AsRef<OsStr>,
+    {
+        let (ours, theirs) = std::os::unix::net::UnixStream::pair()?;
+        ours.set_nonblocking(true)?;
+        let theirs = OwnedFd::from(theirs);
+
+        let mut command = Command::new("/bin/sh");
+        command
+            .arg("-c")
+            .arg(r#"LISTEN_PID=$$ exec "$0" "$@""#)
+            .arg(program)
+            .args(args)
+            .env("LISTEN_FDS", "1")
+            .env("LISTEN_FDNAMES", FD_NAME)
+            .env_remove("LISTEN_PID")
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        let fd = theirs.as_fd().try_clone_to_owned()?;
+        // SAFETY: The closure only makes async-signal-safe system calls.
+        unsafe {
+            command.pre_exec(move || {
+                // SAFETY: The child doesn't use file descriptor 3 for anything else.
+                let mut target = OwnedFd::from_raw_fd(3);
+                let res = dup2(&fd, &mut target).and_then(|_| {
+                    // `dup2` doesn't clear the flag if `fd` already is 3.
+                    fcntl_setfd(&target, FdFlags::empty())
+                });
+                // Dropping it would close it.
+                std::mem::forget(target);
+
+                res.map_err(Into::into)
+            });
+        }
+        let child = command.spawn()?;
+        drop(theirs);
+
+        Ok(Self {
+            stream: UnixStream::from_std(ours)?.into(),
+            child,
+        })
+    }
+
+    /// The spawned child process.
+    pub fn child(&self) -> &Child {
+        &self.child
+    }
+}
+
+impl Socket for Stream {
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> Result<usize, ReplyError> {
+        self.stream.read(buf).await
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> Result<(), ReplyError> {
+        self.stream.write(buf).await
+    }
+
+    async fn read_with_fds<ReplyError>(
+        &mut self,
+        buf: &mut [u8],
+        fds: &mut Vec<OwnedFd>,
+    ) -> Result<usize, ReplyError> {
+        self.stream.read_with_fds(buf, fds).await
+    }
+
+    async fn write_with_fds<ReplyError>(
+        &mut self,
+        buf: &[u8],
+        fds: &[BorrowedFd<'_>],
+    ) -> Result<(), ReplyError> {
+        self.stream.write_with_fds(buf, fds).await
+    }
+
+    fn peer_credentials(&self) -> io::Result<Credentials> {
+        self.stream.peer_credentials()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawn() {
+        let script = r#"printf '%s' "$LISTEN_FDS:$LISTEN_FDNAMES:$LISTEN_PID:$$" >&3"#;
+        let mut stream = Stream::spawn("/bin/sh", ["-c", script]).unwrap();
+        let pid = stream.child().id().unwrap();
+
+        let mut output = Vec::new();
+        let mut buf = [0; 64];
+        loop {
+            let n = stream.read::<&'static str>(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("1:varlink:{pid}:{pid}")
+        );
+    }
+}
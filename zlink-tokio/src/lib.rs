@@ -8,10 +8,14 @@
 #![doc = include_str!("../../README.md")]
 
 pub use zlink::*;
+pub mod exec;
+mod listener;
+pub use listener::{bind, Listener};
+pub mod server;
+mod stream;
+pub use stream::{connect, Stream};
+pub mod systemd;
+pub mod tcp;
+mod tokio_io;
+pub use tokio_io::TokioIo;
 pub mod unix;
-
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {}
-}
@@ -0,0 +1,138 @@
+use std::{fs::Permissions, io, os::unix::fs::PermissionsExt};
+
+use zlink::{address::Transport, server, Address, Result};
+
+use crate::{tcp, unix, Stream};
+
+/// Create a [`Listener`] bound to the given [`Address`].
+///
+/// The transport is picked based on the address. The permissions of the socket file of Unix Domain
+/// Sockets are set from the `mode` parameter of the address, if present. Setting it for an
+/// abstract socket fails, as there's no file, while it's ignored for the other transports. `exec`
+/// addresses can't be listened on.
+///
+/// This must be called from the context of a tokio runtime.
+pub async fn bind(address: Address<'_>) -> Result<Listener, &'static str> {
+    let listener = match (address.transport(), address.mode()) {
+        (Transport::Unix(path), None) => Listener::Unix(unix::bind(path)?),
+        (Transport::Unix(path), Some(mode)) => Listener::Unix(unix::bind_with_permissions(
+            path,
+            Permissions::from_mode(mode),
+        )?),
+        (Transport::UnixAbstract(_), Some(_)) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract sockets have no permissions",
+            )
+            .into())
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        (Transport::UnixAbstract(name), None) => Listener::Unix(unix::bind_abstract(name)?),
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        (Transport::UnixAbstract(_), None) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract sockets not supported",
+            )
+            .into())
+        }
+        (Transport::Tcp { host, port }, _) => Listener::Tcp(tcp::bind((host, port)).await?),
+        (Transport::Exec(_), _) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "can't listen on an `exec` address",
+            )
+            .into())
+        }
+    };
+    Ok(listener)
+}
+
+/// A [`server::Listener`] implementation over any of the supported transports.
+///
+/// Use [`bind`] to create one.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Listener {
+    /// A Unix Domain Socket listener.
+    Unix(unix::Listener),
+    /// A TCP listener.
+    Tcp(tcp::Listener),
+}
+
+impl server::Listener for Listener {
+    type Socket = Stream;
+
+    async fn accept<ReplyError>(&mut self) -> Result<zlink::Connection<Stream>, ReplyError> {
+        let stream = match self {
+            Listener::Unix(listener) => Stream::Unix(listener.accept_stream().await?),
+            Listener::Tcp(listener) => Stream::Tcp(listener.accept_stream().await?),
+        };
+
+        Ok(zlink::Connection::new(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use zlink::server::Listener as _;
+
+    use super::*;
+    use crate::connect;
+
+    #[tokio::test]
+    async fn bind_unix() {
+        let path =
+            std::env::temp_dir().join(format!("zlink-tokio-bind-{}.sock", std::process::id()));
+        let uri = format!("unix:{};mode=0600", path.display());
+        let address = Address::parse(&uri).unwrap();
+        let mut listener = bind(address).await.unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        let (client, server) = tokio::join!(connect(address), listener.accept::<&'static str>());
+        client.unwrap();
+        server.unwrap();
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let name = format!("zlink-tokio-bind-{}", std::process::id());
+            let uri = format!("unix:@{name}");
+            let address = Address::parse(&uri).unwrap();
+            let mut listener = bind(address).await.unwrap();
+
+            let (client, server) =
+                tokio::join!(connect(address), listener.accept::<&'static str>());
+            client.unwrap();
+            server.unwrap();
+
+            // Abstract sockets have no file to set the permissions of.
+            let uri = format!("unix:@{name}-mode;mode=0600");
+            assert!(bind(Address::parse(&uri).unwrap()).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn bind_tcp() {
+        let mut listener = bind(Address::parse("tcp:127.0.0.1:0").unwrap())
+            .await
+            .unwrap();
+        let Listener::Tcp(tcp_listener) = &listener else {
+            panic!("not a TCP listener");
+        };
+        let uri = format!("tcp:{}", tcp_listener.local_addr().unwrap());
+        let address = Address::parse(&uri).unwrap();
+
+        let (client, server) = tokio::join!(connect(address), listener.accept::<&'static str>());
+        client.unwrap();
+        server.unwrap();
+
+        assert!(bind(Address::parse("exec:/bin/true").unwrap())
+            .await
+            .is_err());
+    }
+}
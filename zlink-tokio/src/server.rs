@@ -0,0 +1,350 @@
+//! Provides a server that serves multiple connections concurrently.
+
+use std::{io, num::NonZeroUsize, sync::Arc, time::Duration};
+
+use tokio::{
+    sync::watch,
+    task::{JoinError, JoinSet, LocalSet},
+};
+use zlink::{
+    connection::{Connection, Socket},
+    server::{serve_next, Listener, Service},
+    varlink_service::Info,
+};
+
+/// A server that serves multiple connections concurrently.
+///
+/// Unlike [`zlink::Server`], which serves one connection at a time, this server keeps accepting
+/// new connections while serving the existing ones. Each connection is served by its own task,
+/// with its own service instance. Share state between the service instances through
+/// `Rc`/`Arc` and `RefCell`/`Mutex`.
+///
+/// All the tasks run on the current thread so services don't need to be `Send`.
+///
+/// The server can be shut down gracefully through a [`ShutdownHandle`].
+///
+/// Errors accepting or serving connections don't stop the server. They are logged through
+/// [`tracing`](https://docs.rs/tracing).
+#[derive(Debug)]
+pub struct Server<L> {
+    listener: L,
+    info: Info<'static>,
+    max_connections: Option<NonZeroUsize>,
+    shutdown: Arc<watch::Sender<Option<Duration>>>,
+}
+
+impl<L> Server<L>
+where
+    L: Listener,
+    L::Socket: 'static,
+{
+    /// Create a new server that accepts connections from `listener`.
+    ///
+    /// The server replies to `org.varlink.service.GetInfo` calls with empty service information.
+    /// Use [`Server::with_info`] to provide it.
+    pub fn new(listener: L) -> Self {
+        Self::with_info(listener, Info::default())
+    }
+
+    /// Create a new server that accepts connections from `listener`, with service information.
+    ///
+    /// See [`zlink::Server::with_info`] for details.
+    pub fn with_info(listener: L, info: Info<'static>) -> Self {
+        Self {
+            listener,
+            info,
+            max_connections: None,
+            shutdown: Arc::new(watch::channel(None).0),
+        }
+    }
+
+    /// Set the maximum number of connections served at the same time.
+    ///
+    /// Once the maximum is reached, the server stops accepting new connections until one of the
+    /// existing ones is closed. By default, there is no limit.
+    pub fn set_max_connections(&mut self, max: Option<NonZeroUsize>) {
+        self.max_connections = max;
+    }
+
+    /// The maximum number of connections served at the same time.
+    pub fn max_connections(&self) -> Option<NonZeroUsize> {
+        self.max_connections
+    }
+
+    /// A handle to shut down the server.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            sender: self.shutdown.clone(),
+        }
+    }
+
+    /// Run the server, serving each connection using a clone of `service`.
+    ///
+    /// This method only returns once the server is shut down through a [`ShutdownHandle`].
+    pub async fn run<Srv, ReplyError>(self, service: Srv) -> zlink::Result<(), ReplyError>
+    where
+        Srv: Service + Clone + 'static,
+    {
+        self.run_with(move || service.clone()).await
+    }
+
+    /// Run the server, serving each connection using a service created by `factory`.
+    ///
+    /// This method only returns once the server is shut down through a [`ShutdownHandle`].
+    pub async fn run_with<F, Srv, ReplyError>(self, mut factory: F) -> zlink::Result<(), ReplyError>
+    where
+        F: FnMut() -> Srv,
+        Srv: Service + 'static,
+    {
+        // `spawn_local` requires a `LocalSet`.
+        LocalSet::new().run_until(self.serve(&mut factory)).await
+    }
+
+    async fn serve<F, Srv, ReplyError>(mut self, factory: &mut F) -> zlink::Result<(), ReplyError>
+    where
+        F: FnMut() -> Srv,
+        Srv: Service + 'static,
+    {
+        let mut shutdown = self.shutdown.subscribe();
+        let mut connections = JoinSet::new();
+        let deadline = 'accept: loop {
+            // Reap the connections that were closed in the meantime.
+            while let Some(res) = connections.try_join_next() {
+                log_connection_end(res);
+            }
+            if let Some(max) = self.max_connections {
+                while connections.len() >= max.get() {
+                    tokio::select! {
+                        deadline = shutdown_requested(&mut shutdown) => break 'accept deadline,
+                        Some(res) = connections.join_next() => log_connection_end(res),
+                    }
+                }
+            }
+
+            let accepted = tokio::select! {
+                biased;
+                deadline = shutdown_requested(&mut shutdown) => break 'accept deadline,
+                connection = self.listener.accept::<&'static str>() => connection,
+            };
+            let connection = match accepted {
+                Ok(connection) => connection,
+                Err(e) => {
+                    // Accept errors, e.g running out of file descriptors, are usually transient.
+                    // Retry after a while instead of dropping all the connections.
+                    tracing::warn!("failed to accept a connection: {e}");
+                    tokio::select! {
+                        deadline = shutdown_requested(&mut shutdown) => break 'accept deadline,
+                        _ = tokio::time::sleep(ACCEPT_RETRY_DELAY) => continue,
+                    }
+                }
+            };
+            connections.spawn_local(serve_connection(
+                connection,
+                factory(),
+                self.info,
+                shutdown.clone(),
+            ));
+        };
+
+        // Drain the connections and close the ones that are still busy after the deadline.
+        let drain = async {
+            while let Some(res) = connections.join_next().await {
+                log_connection_end(res);
+            }
+        };
+        if tokio::time::timeout(deadline, drain).await.is_err() {
+            connections.shutdown().await;
+        }
+
+        Ok(())
+    }
+}
+
+/// A handle to gracefully shut down a [`Server`].
+///
+/// Get one through [`Server::shutdown_handle`].
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<Option<Duration>>>,
+}
+
+impl ShutdownHandle {
+    /// Shut down the server.
+    ///
+    /// The server immediately stops accepting new connections and closes the idle ones. The method
+    /// calls being handled still get their replies, including all the replies of calls with the
+    /// `more` flag set. Connections still busy after `deadline` are closed, after which
+    /// [`Server::run`] returns.
+    pub fn shutdown(&self, deadline: Duration) {
+        self.sender.send_replace(Some(deadline));
+    }
+}
+
+// Serve `connection` until it's closed, an error occurs or the server is shut down.
+async fn serve_connection<Sock, Srv>(
+    mut connection: Connection<Sock>,
+    mut service: Srv,
+    info: Info<'static>,
+    mut shutdown: watch::Receiver<Option<Duration>>,
+) -> zlink::Result<(), &'static str>
+where
+    Sock: Socket,
+    Srv: Service,
+{
+    loop {
+        // Only stop while waiting for the next method call, so that the calls in flight get their
+        // replies.
+        tokio::select! {
+            biased;
+            _ = shutdown_requested(&mut shutdown) => return Ok(()),
+            res = connection.wait_for_message() => res?,
+        }
+        serve_next(&mut service, &mut connection, &info).await?;
+    }
+}
+
+// How long to wait before accepting connections again after a failure.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+// Log why a connection was closed, unless the peer closed it or the server was shut down.
+fn log_connection_end(res: Result<zlink::Result<(), &'static str>, JoinError>) {
+    match res {
+        Ok(Ok(())) => (),
+        Ok(Err(zlink::Error::Io(e))) if e.kind() == io::ErrorKind::UnexpectedEof => (),
+        Ok(Err(e)) => tracing::warn!("closing connection after an error: {e}"),
+        Err(e) if e.is_cancelled() => (),
+        Err(e) => tracing::error!("connection task failed: {e}"),
+    }
+}
+
+// Wait until a shutdown is requested and return the deadline for draining the connections.
+async fn shutdown_requested(receiver: &mut watch::Receiver<Option<Duration>>) -> Duration {
+    match receiver.wait_for(Option::is_some).await {
+        Ok(deadline) => deadline.unwrap_or_default(),
+        // The server is gone so there is nothing to wait for anymore.
+        Err(_) => Duration::ZERO,
+    }
+}
+
+#[cfg(test)]
+#[path = "../../tests/ping.rs"]
+mod ping;
+
+#[cfg(test)]
+mod tests {
+    use zlink::varlink_service;
+
+    use super::{
+        ping::{self, InfoReply, Ping},
+        *,
+    };
+
+    #[tokio::test]
+    async fn concurrent_connections() {
+        let path =
+            std::env::temp_dir().join(format!("zlink-tokio-server-{}.sock", std::process::id()));
+        let listener = crate::unix::bind(&path).unwrap();
+        let server = Server::with_info(listener, ping::info());
+
+        let clients = async {
+            // Keep the first connection open while using the second one.
+            let mut first = crate::unix::connect(&path).await.unwrap();
+            let mut second = crate::unix::connect(&path).await.unwrap();
+            for connection in [&mut second, &mut first] {
+                let reply = connection
+                    .call_method::<_, InfoReply<'_>, varlink_service::Error>(
+                        varlink_service::Method::GetInfo,
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(reply.parameters().unwrap().product, "ping");
+            }
+        };
+
+        tokio::select! {
+            res = server.run::<_, &'static str>(Ping) => panic!("server exited: {res:?}"),
+            _ = clients => (),
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown() {
+        let path =
+            std::env::temp_dir().join(format!("zlink-tokio-shutdown-{}.sock", std::process::id()));
+        let listener = crate::unix::bind(&path).unwrap();
+        let server = Server::new(listener);
+        let handle = server.shutdown_handle();
+
+        let client = async {
+            let mut connection = crate::unix::connect(&path).await.unwrap();
+            connection
+                .call_method::<_, InfoReply<'_>, varlink_service::Error>(
+                    varlink_service::Method::GetInfo,
+                )
+                .await
+                .unwrap();
+            handle.shutdown(Duration::from_secs(1));
+
+            // The idle connection gets closed.
+            connection
+                .receive_reply::<InfoReply<'_>, varlink_service::Error>()
+                .await
+                .unwrap_err();
+        };
+
+        let (res, ()) = tokio::join!(server.run::<_, &'static str>(Ping), client);
+        res.unwrap();
+    }
+
+    #[tokio::test]
+    async fn accept_error() {
+        let path =
+            std::env::temp_dir().join(format!("zlink-tokio-accept-{}.sock", std::process::id()));
+        let listener = FailingListener {
+            listener: crate::unix::bind(&path).unwrap(),
+            failures: 2,
+        };
+        let server = Server::new(listener);
+
+        // The server keeps accepting connections after failing to.
+        let client = async {
+            let mut connection = crate::unix::connect(&path).await.unwrap();
+            connection
+                .call_method::<_, InfoReply<'_>, varlink_service::Error>(
+                    varlink_service::Method::GetInfo,
+                )
+                .await
+                .unwrap();
+        };
+
+        tokio::select! {
+            res = server.run::<_, &'static str>(Ping) => panic!("server exited: {res:?}"),
+            _ = client => (),
+        }
+    }
+
+    // A listener that fails to accept the first `failures` connections.
+    #[derive(Debug)]
+    struct FailingListener<L> {
+        listener: L,
+        failures: usize,
+    }
+
+    impl<L> Listener for FailingListener<L>
+    where
+        L: Listener,
+    {
+        type Socket = L::Socket;
+
+        async fn accept<ReplyError>(
+            &mut self,
+        ) -> zlink::Result<zlink::Connection<Self::Socket>, ReplyError> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(zlink::Error::SocketRead);
+            }
+
+            self.listener.accept().await
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    io,
+    os::fd::{BorrowedFd, OwnedFd},
+};
+
+use tokio::net::{TcpStream, UnixStream};
+use zlink::{
+    address::Transport,
+    connection::{Credentials, Socket},
+    Address, Result,
+};
+
+use crate::{exec, tcp, unix};
+
+/// Connect to the service at the given [`Address`].
+///
+/// The transport is picked based on the address.
+pub async fn connect(address: Address<'_>) -> Result<zlink::Connection<Stream>, &'static str> {
+    let stream = match address.transport() {
+        Transport::Unix(path) => Stream::Unix(UnixStream::connect(path).await?.into()),
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Transport::UnixAbstract(name) => Stream::Unix(unix::connect_abstract_stream(name)?.into()),
+        Transport::Tcp { host, port } => {
+            Stream::Tcp(tcp::Stream::new(TcpStream::connect((host, port)).await?)?)
+        }
+        Transport::Exec(program) => {
+            Stream::Exec(exec::Stream::spawn(program, std::iter::empty::<&str>())?)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        Transport::UnixAbstract(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract sockets not supported",
+            )
+            .into())
+        }
+    };
+
+    Ok(zlink::Connection::new(stream))
+}
+
+/// A [`Socket`] implementation over any of the supported transports.
+///
+/// Use [`connect`] to create a connection using it.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Stream {
+    /// A Unix Domain Socket.
+    Unix(unix::Stream),
+    /// A TCP socket.
+    Tcp(tcp::Stream),
+    /// A spawned service executable, see [`exec`].
+    Exec(exec::Stream),
+}
+
+impl Socket for Stream {
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> Result<usize, ReplyError> {
+        match self {
+            Stream::Unix(stream) => stream.read(buf).await,
+            Stream::Tcp(stream) => stream.read(buf).await,
+            Stream::Exec(stream) => stream.read(buf).await,
+        }
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> Result<(), ReplyError> {
+        match self {
+            Stream::Unix(stream) => stream.write(buf).await,
+            Stream::Tcp(stream) => stream.write(buf).await,
+            Stream::Exec(stream) => stream.write(buf).await,
+        }
+    }
+
+    async fn read_with_fds<ReplyError>(
+        &mut self,
+        buf: &mut [u8],
+        fds: &mut Vec<OwnedFd>,
+    ) -> Result<usize, ReplyError> {
+        match self {
+            Stream::Unix(stream) => stream.read_with_fds(buf, fds).await,
+            Stream::Tcp(stream) => stream.read_with_fds(buf, fds).await,
+            Stream::Exec(stream) => stream.read_with_fds(buf, fds).await,
+        }
+    }
+
+    async fn write_with_fds<ReplyError>(
+        &mut self,
+        buf: &[u8],
+        fds: &[BorrowedFd<'_>],
+    ) -> Result<(), ReplyError> {
+        match self {
+            Stream::Unix(stream) => stream.write_with_fds(buf, fds).await,
+            Stream::Tcp(stream) => stream.write_with_fds(buf, fds).await,
+            Stream::Exec(stream) => stream.write_with_fds(buf, fds).await,
+        }
+    }
+
+    fn peer_credentials(&self) -> io::Result<Credentials> {
+        match self {
+            Stream::Unix(stream) => stream.peer_credentials(),
+            Stream::Tcp(stream) => stream.peer_credentials(),
+            Stream::Exec(stream) => stream.peer_credentials(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zlink::server::Listener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn connect_unix() {
+        let path =
+            std::env::temp_dir().join(format!("zlink-tokio-connect-{}.sock", std::process::id()));
+        let mut listener = unix::bind(&path).unwrap();
+        let uri = format!("unix:{}", path.display());
+        let address = Address::parse(&uri).unwrap();
+
+        let (client, server) = tokio::join!(connect(address), listener.accept::<&'static str>());
+        client.unwrap();
+        server.unwrap();
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let name = format!("zlink-tokio-connect-{}", std::process::id());
+            let mut listener = unix::bind_abstract(&name).unwrap();
+            let uri = format!("unix:@{name}");
+            let address = Address::parse(&uri).unwrap();
+
+            let (client, server) =
+                tokio::join!(connect(address), listener.accept::<&'static str>());
+            client.unwrap();
+            server.unwrap();
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! Provides support for systemd socket activation.
+//!
+//! With socket activation, systemd creates the sockets and passes them to the service as file
+//! descriptors, through the `LISTEN_FDS`, `LISTEN_PID` and `LISTEN_FDNAMES` environment variables.
+//! With `Accept=no` (the default), the passed sockets are listening and [`Listener::from_env`]
+//! picks one to serve. With `Accept=yes`, a service instance is spawned for each connection and
+//! [`connection_from_env`] gives access to it.
+//!
+//! See [`sd_listen_fds(3)`][sd_listen_fds] for details.
+//!
+//! [sd_listen_fds]: https://www.freedesktop.org/software/systemd/man/latest/sd_listen_fds.html
+
+use std::{
+    env, io,
+    os::fd::{FromRawFd, OwnedFd, RawFd},
+};
+
+use rustix::{
+    io::{fcntl_setfd, FdFlags},
+    net::{getsockname, sockopt, AddressFamily},
+};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use zlink::{server, Result};
+
+use crate::{tcp, Stream};
+
+// The first file descriptor passed by systemd.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Take the file descriptors passed by systemd, along with their names.
+///
+/// The names are set through `FileDescriptorName=` in the socket unit and default to `unknown`. An
+/// empty list is returned if no file descriptors were passed to this process.
+///
+/// The environment variables are unset, so that the file descriptors are only taken once and
+/// aren't inherited by child processes. Like all environment modifications, this is not safe to
+/// do while other threads access the environment.
+pub fn listen_fds() -> io::Result<Vec<(OwnedFd, String)>> {
+    let fds = env_fds()?;
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(name);
+    }
+
+    fds.into_iter()
+        .map(|(fd, name)| {
+            // SAFETY: systemd passes these file descriptors to us and we've just made sure no one
+            // else takes them, by unsetting the environment variables.
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+            fcntl_setfd(&fd, FdFlags::CLOEXEC)?;
+
+            Ok((fd, name))
+        })
+        .collect()
+}
+
+// The file descriptors passed to this process and their names, without taking them.
+pub(crate) fn env_fds() -> io::Result<Vec<(RawFd, String)>> {
+    parse_env(
+        env::var("LISTEN_PID").ok().as_deref(),
+        env::var("LISTEN_FDS").ok().as_deref(),
+        env::var("LISTEN_FDNAMES").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+/// Create a [`zlink::Connection`] for the connected socket passed by systemd in `Accept=yes` mode.
+///
+/// This fails if not exactly one file descriptor was passed. See [`listen_fds`] for the handling
+/// of the environment. This must be called from the context of a tokio runtime.
+pub fn connection_from_env() -> Result<zlink::Connection<Stream>, &'static str> {
+    let mut fds = listen_fds()?;
+    if fds.len() != 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "expected exactly one file descriptor from systemd",
+        )
+        .into());
+    }
+    let (fd, _) = fds.remove(0);
+
+    connection_from_fd(fd)
+}
+
+/// Create a [`zlink::Connection`] from a connected Unix Domain or TCP socket.
+///
+/// This must be called from the context of a tokio runtime.
+pub fn connection_from_fd(fd: OwnedFd) -> Result<zlink::Connection<Stream>, &'static str> {
+    if sockopt::socket_acceptconn(&fd)? {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "socket is listening").into());
+    }
+    let stream = match socket_family(&fd)? {
+        Family::Unix => {
+            let stream = std::os::unix::net::UnixStream::from(fd);
+            stream.set_nonblocking(true)?;
+
+            Stream::Unix(UnixStream::from_std(stream)?.into())
+        }
+        Family::Inet => {
+            let stream = std::net::TcpStream::from(fd);
+            stream.set_nonblocking(true)?;
+
+            Stream::Tcp(tcp::Stream::new(TcpStream::from_std(stream)?)?)
+        }
+    };
+
+    Ok(zlink::Connection::new(stream))
+}
+
+/// A [`server::Listener`] implementation using a listening socket passed by systemd.
+///
+/// Both Unix Domain and TCP sockets are supported.
+#[derive(Debug)]
+pub struct Listener(Inner);
+
+impl Listener {
+    /// Create a listener from the listening socket passed by systemd.
+    ///
+    /// If `name` is `Some`, the socket with that name is used, otherwise the first one. The other
+    /// sockets are closed. See [`listen_fds`] for the handling of the environment and use
+    /// [`Listener::from_fd`] to serve multiple sockets.
+    ///
+    /// This must be called from the context of a tokio runtime.
+    pub fn from_env(name: Option<&str>) -> Result<Self, &'static str> {
+        let fd = listen_fds()?
+            .into_iter()
+            .find(|(_, fd_name)| name.map_or(true, |name| name == fd_name))
+            .map(|(fd, _)| fd)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "no matching socket from systemd")
+            })?;
+
+        Self::from_fd(fd)
+    }
+
+    /// Create a listener from a listening Unix Domain or TCP socket.
+    ///
+    /// This must be called from the context of a tokio runtime.
+    pub fn from_fd(fd: OwnedFd) -> Result<Self, &'static str> {
+        if !sockopt::socket_acceptconn(&fd)? {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "socket isn't listening").into(),
+            );
+        }
+        let inner = match socket_family(&fd)? {
+            Family::Unix => {
+                let listener = std::os::unix::net::UnixListener::from(fd);
+                listener.set_nonblocking(true)?;
+
+                Inner::Unix(UnixListener::from_std(listener)?)
+            }
+            Family::Inet => {
+                let listener = std::net::TcpListener::from(fd);
+                listener.set_nonblocking(true)?;
+
+                Inner::Tcp(TcpListener::from_std(listener)?)
+            }
+        };
+
+        Ok(Self(inner))
+    }
+}
+
+impl server::Listener for Listener {
+    type Socket = Stream;
+
+    async fn accept<ReplyError>(&mut self) -> Result<zlink::Connection<Stream>, ReplyError> {
+        let stream = match &self.0 {
+            Inner::Unix(listener) => Stream::Unix(listener.accept().await?.0.into()),
+            Inner::Tcp(listener) => Stream::Tcp(tcp::Stream::new(listener.accept().await?.0)?),
+        };
+
+        Ok(zlink::Connection::new(stream))
+    }
+}
+
+#[derive(Debug)]
+enum Inner {
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+enum Family {
+    Unix,
+    Inet,
+}
+
+// The family of the socket `fd`, if supported.
+fn socket_family(fd: &OwnedFd) -> io::Result<Family> {
+    match getsockname(fd)?.address_family() {
+        AddressFamily::UNIX => Ok(Family::Unix),
+        AddressFamily::INET | AddressFamily::INET6 => Ok(Family::Inet),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "unsupported socket family",
+        )),
+    }
+}
+
+// Parse the socket activation environment variables into file descriptors and their names.
+fn parse_env(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    listen_fdnames: Option<&str>,
+    pid: u32,
+) -> io::Result<Vec<(RawFd, String)>> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(Vec::new());
+    };
+    let listen_pid: u32 = listen_pid
+        .parse()
+        .map_err(|_| invalid("invalid LISTEN_PID"))?;
+    if listen_pid != pid {
+        // The variables are meant for a different process, e.g our parent.
+        return Ok(Vec::new());
+    }
+    let count: RawFd = listen_fds
+        .parse()
+        .ok()
+        .filter(|count| *count >= 0 && *count <= RawFd::MAX - LISTEN_FDS_START)
+        .ok_or_else(|| invalid("invalid LISTEN_FDS"))?;
+
+    let mut names = listen_fdnames.map(|names| names.split(':'));
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            let name = match &mut names {
+                Some(names) => names
+                    .next()
+                    .ok_or_else(|| invalid("LISTEN_FDNAMES doesn't match LISTEN_FDS"))?,
+                None => "unknown",
+            };
+
+            Ok((fd, name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsFd;
+
+    use zlink::server::Listener as _;
+
+    use super::*;
+
+    #[test]
+    fn env() {
+        let pid = std::process::id();
+        let own_pid = pid.to_string();
+        assert!(parse_env(None, None, None, pid).unwrap().is_empty());
+        assert!(parse_env(Some("1"), Some("2"), None, pid)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            parse_env(Some(&own_pid), Some("2"), None, pid).unwrap(),
+            [(3, "unknown".to_string()), (4, "unknown".to_string())]
+        );
+        assert_eq!(
+            parse_env(Some(&own_pid), Some("2"), Some("varlink:metrics"), pid).unwrap(),
+            [(3, "varlink".to_string()), (4, "metrics".to_string())]
+        );
+        assert!(parse_env(Some(&own_pid), Some("2"), Some("varlink"), pid).is_err());
+        assert!(parse_env(Some(&own_pid), Some("-1"), None, pid).is_err());
+        assert!(parse_env(Some("self"), Some("1"), None, pid).is_err());
+    }
+
+    #[tokio::test]
+    async fn from_fd() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let fd = OwnedFd::from(listener);
+
+        // A listening socket can't be used as a connection.
+        let dup = fd.as_fd().try_clone_to_owned().unwrap();
+        assert!(connection_from_fd(dup).is_err());
+
+        let mut listener = Listener::from_fd(fd).unwrap();
+        let client = std::net::TcpStream::connect(address).unwrap();
+        let server = listener.accept::<&'static str>().await.unwrap();
+        assert!(matches!(server.socket(), Stream::Tcp(_)));
+
+        // A connected socket can't be used as a listener.
+        let fd = OwnedFd::from(client);
+        let dup = fd.as_fd().try_clone_to_owned().unwrap();
+        assert!(Listener::from_fd(dup).is_err());
+        let client = connection_from_fd(fd).unwrap();
+        assert!(matches!(client.socket(), Stream::Tcp(_)));
+
+        let (client, server) = std::os::unix::net::UnixStream::pair().unwrap();
+        let client = connection_from_fd(client.into()).unwrap();
+        assert!(matches!(client.socket(), Stream::Unix(_)));
+        drop(server);
+    }
+}
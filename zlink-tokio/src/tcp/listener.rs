@@ -0,0 +1,66 @@
+use std::{io, net::SocketAddr, time::Duration};
+
+use tokio::net::{TcpListener, ToSocketAddrs};
+use zlink::{server, Result};
+
+use super::{set_keepalive, Connection, Stream};
+
+/// Create a [`Listener`] bound to the given TCP address.
+pub async fn bind<A>(address: A) -> Result<Listener, &'static str>
+where
+    A: ToSocketAddrs,
+{
+    let listener = TcpListener::bind(address).await?;
+
+    Ok(Listener {
+        listener,
+        nodelay: true,
+        keepalive: None,
+    })
+}
+
+/// A [`server::Listener`] implementation using TCP.
+///
+/// Use [`bind`] to create one. By default, `TCP_NODELAY` is enabled and keepalive is disabled on
+/// the accepted connections.
+#[derive(Debug)]
+pub struct Listener {
+    listener: TcpListener,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+}
+
+impl Listener {
+    /// The local address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Enable or disable `TCP_NODELAY` on the accepted connections.
+    pub fn set_nodelay(&mut self, nodelay: bool) {
+        self.nodelay = nodelay;
+    }
+
+    /// Set the keepalive idle time of the accepted connections.
+    ///
+    /// See [`Stream::set_keepalive`] for details.
+    pub fn set_keepalive(&mut self, idle: Option<Duration>) {
+        self.keepalive = idle;
+    }
+
+    pub(crate) async fn accept_stream(&mut self) -> io::Result<Stream> {
+        let (stream, _) = self.listener.accept().await?;
+        stream.set_nodelay(self.nodelay)?;
+        set_keepalive(&stream, self.keepalive)?;
+
+        Ok(Stream(stream))
+    }
+}
+
+impl server::Listener for Listener {
+    type Socket = Stream;
+
+    async fn accept<ReplyError>(&mut self) -> Result<Connection, ReplyError> {
+        Ok(Connection::new(self.accept_stream().await?))
+    }
+}
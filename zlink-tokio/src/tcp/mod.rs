@@ -0,0 +1,132 @@
+//! Provides transport over TCP.
+
+mod listener;
+pub use listener::{bind, Listener};
+
+use std::{io, net::SocketAddr, time::Duration};
+
+use rustix::net::sockopt;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, ToSocketAddrs},
+};
+use zlink::{connection::Socket, Result};
+
+/// The connection type that uses TCP for transport.
+pub type Connection = zlink::Connection<Stream>;
+
+/// Connect to the TCP socket at the given address.
+///
+/// `TCP_NODELAY` is enabled on the socket, since Varlink messages are typically small and latency
+/// sensitive. Use [`Stream::set_nodelay`] to disable it.
+pub async fn connect<A>(address: A) -> Result<Connection, &'static str>
+where
+    A: ToSocketAddrs,
+{
+    let stream = Stream::new(TcpStream::connect(address).await?)?;
+
+    Ok(Connection::new(stream))
+}
+
+/// The [`Socket`] implementation using TCP.
+#[derive(Debug)]
+pub struct Stream(TcpStream);
+
+impl Stream {
+    /// Create a new stream from a connected [`TcpStream`], enabling `TCP_NODELAY` on it.
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+
+        Ok(Self(stream))
+    }
+
+    /// The address of the peer.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    /// Enable or disable `TCP_NODELAY`.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.0.set_nodelay(nodelay)
+    }
+
+    /// If `TCP_NODELAY` is enabled.
+    pub fn nodelay(&self) -> io::Result<bool> {
+        self.0.nodelay()
+    }
+
+    /// Enable or disable TCP keepalive.
+    ///
+    /// If `idle` is `Some`, keepalive probes are sent after the connection has been idle for
+    /// the given duration. Keepalive is disabled if it's `None`.
+    pub fn set_keepalive(&self, idle: Option<Duration>) -> io::Result<()> {
+        set_keepalive(&self.0, idle)
+    }
+
+    /// The idle time after which keepalive probes are sent, if keepalive is enabled.
+    pub fn keepalive(&self) -> io::Result<Option<Duration>> {
+        if !sockopt::socket_keepalive(&self.0)? {
+            return Ok(None);
+        }
+
+        sockopt::tcp_keepidle(&self.0).map(Some).map_err(Into::into)
+    }
+}
+
+impl Socket for Stream {
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> Result<usize, ReplyError> {
+        self.0.read(buf).await.map_err(Into::into)
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> Result<(), ReplyError> {
+        let mut pos = 0;
+
+        while pos < buf.len() {
+            let n = self.0.write(&buf[pos..]).await?;
+            pos += n;
+        }
+
+        Ok(())
+    }
+}
+
+// Enable keepalive on `stream` with the given idle time, or disable it.
+fn set_keepalive(stream: &TcpStream, idle: Option<Duration>) -> io::Result<()> {
+    sockopt::set_socket_keepalive(stream, idle.is_some())?;
+    if let Some(idle) = idle {
+        sockopt::set_tcp_keepidle(stream, idle)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn settings() {
+        let mut listener = bind("127.0.0.1:0").await.unwrap();
+        listener.set_keepalive(Some(Duration::from_secs(60)));
+        let address = listener.local_addr().unwrap();
+
+        let (client, server) = tokio::join!(
+            connect(address),
+            zlink::server::Listener::accept::<&'static str>(&mut listener)
+        );
+        let client = client.unwrap();
+        let server = server.unwrap();
+
+        let stream = client.socket();
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(stream.keepalive().unwrap(), None);
+        stream.set_nodelay(false).unwrap();
+        assert!(!stream.nodelay().unwrap());
+
+        let stream = server.socket();
+        assert!(stream.nodelay().unwrap());
+        assert_eq!(stream.keepalive().unwrap(), Some(Duration::from_secs(60)));
+        stream.set_keepalive(None).unwrap();
+        assert_eq!(stream.keepalive().unwrap(), None);
+    }
+}
@@ -0,0 +1,83 @@
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use zlink::{connection::Socket, Result};
+
+/// A [`Socket`] implementation for any [`tokio::io`] I/O type.
+///
+/// This allows using any type implementing both [`AsyncRead`] and [`AsyncWrite`] as a transport,
+/// e.g pipes, TLS streams or serial ports. The data is flushed after each write. For
+/// [`futures_io`](https://docs.rs/futures-io) types, use [`zlink::connection::FuturesIo`] instead.
+#[derive(Debug)]
+pub struct TokioIo<T>(T);
+
+impl<T> TokioIo<T> {
+    /// Create a new socket wrapping `io`.
+    pub fn new(io: T) -> Self {
+        Self(io)
+    }
+
+    /// The wrapped I/O type.
+    pub fn get_ref(&self) -> &T {
+        &self.0
+    }
+
+    /// The wrapped I/O type, mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    /// Consume the socket and return the wrapped I/O type.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Socket for TokioIo<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> Result<usize, ReplyError> {
+        self.0.read(buf).await.map_err(Into::into)
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> Result<(), ReplyError> {
+        self.0.write_all(buf).await?;
+
+        self.0.flush().await.map_err(Into::into)
+    }
+}
+
+impl<T> From<T> for TokioIo<T> {
+    fn from(io: T) -> Self {
+        Self(io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn duplex() {
+        // A small buffer to exercise partial reads and writes.
+        let (client, server) = tokio::io::duplex(16);
+        let mut client = zlink::Connection::new(TokioIo::new(client));
+        let mut server = zlink::Connection::new(TokioIo::new(server));
+
+        let send =
+            client.send_call::<_, &'static str>(Method::Echo { text: "hello" }, None, None, None);
+        let receive = server.receive_call::<Method<'_>, &'static str>();
+        let (sent, call) = tokio::join!(send, receive);
+        sent.unwrap();
+        let Method::Echo { text } = call.unwrap().into_method();
+        assert_eq!(text, "hello");
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "method", content = "parameters")]
+    enum Method<'a> {
+        #[serde(rename = "org.example.echo.Echo")]
+        Echo { text: &'a str },
+    }
+}
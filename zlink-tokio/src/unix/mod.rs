@@ -1,10 +1,23 @@
 //! Provides transport over Unix Domain Sockets.
 
+mod listener;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use listener::bind_abstract;
+pub use listener::{bind, bind_with_permissions, Listener};
+
+use std::{
+    io,
+    os::fd::{BorrowedFd, OwnedFd},
+};
+
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncReadExt, AsyncWriteExt, Interest},
     net::UnixStream,
 };
-use zlink::{connection::Socket, Result};
+use zlink::{
+    connection::{Credentials, Socket},
+    Result,
+};
 
 /// The connection type that uses Unix Domain Sockets for transport.
 pub type Connection = zlink::Connection<Stream>;
@@ -21,7 +34,38 @@ where
         .map_err(Into::into)
 }
 
+/// Connect to the abstract Unix Domain Socket with the given name.
+///
+/// The name is given without the leading `@` (or NUL byte).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub async fn connect_abstract<N>(name: N) -> Result<Connection, &'static str>
+where
+    N: AsRef<[u8]>,
+{
+    connect_abstract_stream(name)
+        .map(Stream)
+        .map(Connection::new)
+        .map_err(Into::into)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn connect_abstract_stream<N>(name: N) -> io::Result<UnixStream>
+where
+    N: AsRef<[u8]>,
+{
+    use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+
+    let address = SocketAddr::from_abstract_name(name)?;
+    // Connecting to a Unix Domain Socket doesn't block for long so no need for a blocking task.
+    let stream = std::os::unix::net::UnixStream::connect_addr(&address)?;
+    stream.set_nonblocking(true)?;
+
+    UnixStream::from_std(stream)
+}
+
 /// The [`Socket`] implementation using Unix Domain Sockets.
+///
+/// This supports passing file descriptors along with the messages.
 #[derive(Debug)]
 pub struct Stream(UnixStream);
 
@@ -40,6 +84,57 @@ impl Socket for Stream {
 
         Ok(())
     }
+
+    async fn read_with_fds<ReplyError>(
+        &mut self,
+        buf: &mut [u8],
+        fds: &mut Vec<OwnedFd>,
+    ) -> Result<usize, ReplyError> {
+        let stream = &self.0;
+        let bytes_read = stream
+            .async_io(Interest::READABLE, || {
+                zlink::unix::recv_with_fds(stream, &mut *buf, &mut *fds)
+            })
+            .await?;
+
+        Ok(bytes_read)
+    }
+
+    async fn write_with_fds<ReplyError>(
+        &mut self,
+        buf: &[u8],
+        fds: &[BorrowedFd<'_>],
+    ) -> Result<(), ReplyError> {
+        if fds.is_empty() {
+            return self.write(buf).await;
+        }
+
+        // The file descriptors are sent along with the first chunk of data.
+        let stream = &self.0;
+        let written = stream
+            .async_io(Interest::WRITABLE, || {
+                zlink::unix::send_with_fds(stream, buf, fds)
+            })
+            .await?;
+
+        self.write(&buf[written..]).await
+    }
+
+    fn peer_credentials(&self) -> io::Result<Credentials> {
+        let credentials = self.0.peer_cred()?;
+        let pid = credentials.pid();
+        #[cfg(target_os = "linux")]
+        let pidfd = zlink::unix::peer_pidfd(&self.0);
+        #[cfg(not(target_os = "linux"))]
+        let pidfd = None;
+
+        Ok(Credentials::new(
+            credentials.uid(),
+            credentials.gid(),
+            pid,
+            pidfd,
+        ))
+    }
 }
 
 impl From<UnixStream> for Stream {
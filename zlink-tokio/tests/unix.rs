@@ -0,0 +1,14 @@
+use zlink_tokio as rt;
+
+#[path = "../../tests/ping.rs"]
+mod ping;
+#[path = "../../tests/unix.rs"]
+mod suite;
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
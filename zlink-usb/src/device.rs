@@ -0,0 +1,110 @@
+use std::io;
+
+use nusb::DeviceInfo;
+use zlink::{mux::Multiplexer, Result};
+
+use crate::{BulkEndpoints, Connection, Stream};
+
+/// Criteria for finding the USB device and interface to connect to.
+///
+/// All the criteria that are set must match. The `class`, `subclass` and `protocol` criteria
+/// apply to the interface, not the device.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceFilter {
+    /// The vendor ID of the device.
+    pub vendor_id: Option<u16>,
+    /// The product ID of the device.
+    pub product_id: Option<u16>,
+    /// The class of the interface.
+    pub class: Option<u8>,
+    /// The subclass of the interface.
+    pub subclass: Option<u8>,
+    /// The protocol of the interface.
+    pub protocol: Option<u8>,
+}
+
+impl DeviceFilter {
+    /// Find the first device and the number of its first interface that match.
+    pub fn find(&self) -> io::Result<Option<(DeviceInfo, u8)>> {
+        for device in nusb::list_devices()? {
+            if !self.matches_device(device.vendor_id(), device.product_id()) {
+                continue;
+            }
+            let interface = device
+                .interfaces()
+                .find(|i| self.matches_interface(i.class(), i.subclass(), i.protocol()))
+                .map(|i| i.interface_number());
+            if let Some(interface) = interface {
+                return Ok(Some((device, interface)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn matches_device(&self, vendor_id: u16, product_id: u16) -> bool {
+        self.vendor_id.map_or(true, |id| id == vendor_id)
+            && self.product_id.map_or(true, |id| id == product_id)
+    }
+
+    fn matches_interface(&self, class: u8, subclass: u8, protocol: u8) -> bool {
+        self.class.map_or(true, |c| c == class)
+            && self.subclass.map_or(true, |s| s == subclass)
+            && self.protocol.map_or(true, |p| p == protocol)
+    }
+}
+
+/// Connect to the first USB device and interface matching `filter`.
+///
+/// The interface is claimed and its first bulk IN and OUT endpoints are used for the transport.
+pub fn connect(filter: &DeviceFilter) -> Result<Connection, &'static str> {
+    let endpoints = open(filter)?;
+
+    Ok(Connection::new(Stream::new(endpoints)))
+}
+
+/// Connect to the first USB device and interface matching `filter`, multiplexing connections.
+///
+/// This is for devices using the multiplexer of `zlink-micro`, allowing up to `N` connections at
+/// the same time. Returns the multiplexer to create the connections from, along with the reading
+/// side of the transport. The latter needs to be passed to [`Multiplexer::run`], which must be
+/// running for the connections to receive anything.
+///
+/// The interface is picked the same way as in [`connect`].
+pub fn connect_multiplexed<const N: usize>(
+    filter: &DeviceFilter,
+) -> Result<(Multiplexer<Stream, N>, Stream), &'static str> {
+    let endpoints = open(filter)?;
+    let reader = Stream::new(endpoints.clone());
+
+    Ok((Multiplexer::new(Stream::new(endpoints)), reader))
+}
+
+// Claim the first interface matching `filter` and get its endpoints.
+fn open(filter: &DeviceFilter) -> io::Result<BulkEndpoints> {
+    let (device, interface) = filter
+        .find()?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no matching USB device"))?;
+    let interface = device.open()?.claim_interface(interface)?;
+
+    BulkEndpoints::new(interface)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter() {
+        let filter = DeviceFilter {
+            vendor_id: Some(0x1209),
+            class: Some(0xff),
+            ..DeviceFilter::default()
+        };
+        assert!(filter.matches_device(0x1209, 0x0001));
+        assert!(!filter.matches_device(0x1d6b, 0x0001));
+        assert!(filter.matches_interface(0xff, 0, 0));
+        assert!(!filter.matches_interface(0x08, 0, 0));
+        assert!(DeviceFilter::default().matches_interface(0x08, 6, 80));
+    }
+}
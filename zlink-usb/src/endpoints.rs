@@ -0,0 +1,103 @@
+use core::future::Future;
+use std::io;
+
+use nusb::{
+    transfer::{Direction, EndpointType, RequestBuffer},
+    Interface,
+};
+
+/// The USB endpoint I/O needed by [`Stream`](crate::Stream).
+///
+/// [`BulkEndpoints`] is the implementation for actual devices. This trait mainly exists to allow
+/// testing against a mock of the endpoints.
+pub trait Endpoints {
+    /// Receive a transfer of up to `len` bytes from the IN endpoint.
+    ///
+    /// `len` is always a multiple of [`Endpoints::max_packet_size`]. An empty transfer is
+    /// returned if the device sends a zero-length packet.
+    fn receive(&mut self, len: usize) -> impl Future<Output = io::Result<Vec<u8>>>;
+
+    /// Send `data` as a transfer to the OUT endpoint.
+    fn send(&mut self, data: Vec<u8>) -> impl Future<Output = io::Result<()>>;
+
+    /// The maximum packet size of the endpoints.
+    fn max_packet_size(&self) -> usize;
+}
+
+/// The [`Endpoints`] implementation using the bulk endpoints of a USB interface.
+///
+/// Clones share the interface, so that one clone can be used for reading while another one is
+/// used for writing.
+#[derive(Debug, Clone)]
+pub struct BulkEndpoints {
+    interface: Interface,
+    in_address: u8,
+    out_address: u8,
+    max_packet_size: usize,
+}
+
+impl BulkEndpoints {
+    /// Use the first bulk IN and OUT endpoints of the claimed `interface`.
+    pub fn new(interface: Interface) -> io::Result<Self> {
+        let mut in_endpoint = None;
+        let mut out_endpoint = None;
+        for endpoint in interface
+            .descriptors()
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no interface descriptor"))?
+            .endpoints()
+            .filter(|endpoint| endpoint.transfer_type() == EndpointType::Bulk)
+        {
+            let slot = match endpoint.direction() {
+                Direction::In => &mut in_endpoint,
+                Direction::Out => &mut out_endpoint,
+            };
+            if slot.is_none() {
+                *slot = Some((endpoint.address(), endpoint.max_packet_size()));
+            }
+        }
+        let (Some((in_address, in_size)), Some((out_address, out_size))) =
+            (in_endpoint, out_endpoint)
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "interface doesn't have bulk IN and OUT endpoints",
+            ));
+        };
+
+        Ok(Self {
+            interface,
+            in_address,
+            out_address,
+            max_packet_size: in_size.min(out_size),
+        })
+    }
+
+    /// The USB interface.
+    pub fn interface(&self) -> &Interface {
+        &self.interface
+    }
+}
+
+impl Endpoints for BulkEndpoints {
+    async fn receive(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        self.interface
+            .bulk_in(self.in_address, RequestBuffer::new(len))
+            .await
+            .into_result()
+            .map_err(io::Error::other)
+    }
+
+    async fn send(&mut self, data: Vec<u8>) -> io::Result<()> {
+        self.interface
+            .bulk_out(self.out_address, data)
+            .await
+            .into_result()
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+
+    fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+}
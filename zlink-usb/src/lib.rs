@@ -0,0 +1,21 @@
+// `nusb` only supports these platforms.
+#![cfg(any(target_os = "linux", target_os = "macos", windows))]
+#![deny(
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_idioms,
+    missing_docs
+)]
+#![warn(unreachable_pub)]
+#![doc = include_str!("../../README.md")]
+
+pub use zlink::*;
+mod device;
+pub use device::{connect, connect_multiplexed, DeviceFilter};
+mod endpoints;
+pub use endpoints::{BulkEndpoints, Endpoints};
+mod stream;
+pub use stream::Stream;
+
+/// The connection type that uses USB for transport.
+pub type Connection<E = BulkEndpoints> = zlink::Connection<Stream<E>>;
@@ -0,0 +1,287 @@
+use zlink::{connection::Socket, Result};
+
+use crate::{BulkEndpoints, Endpoints};
+
+/// The [`Socket`] implementation over a pair of USB bulk endpoints.
+///
+/// Each write is sent as a single USB transfer. If its length is a multiple of the maximum packet
+/// size, it's followed by a zero-length packet so that the device knows where the transfer ends.
+/// On the reading side, the data of the transfers is handed to the [`zlink::Connection`] as a byte
+/// stream, so messages can span multiple packets and transfers and a transfer can contain
+/// multiple messages. Zero-length packets are skipped.
+#[derive(Debug)]
+pub struct Stream<E = BulkEndpoints> {
+    endpoints: E,
+    // Received data that didn't fit in the buffer of the last read.
+    pending: Vec<u8>,
+    pending_pos: usize,
+}
+
+impl<E> Stream<E>
+where
+    E: Endpoints,
+{
+    /// Create a new stream over `endpoints`.
+    pub fn new(endpoints: E) -> Self {
+        Self {
+            endpoints,
+            pending: Vec::new(),
+            pending_pos: 0,
+        }
+    }
+
+    /// The endpoints.
+    pub fn endpoints(&self) -> &E {
+        &self.endpoints
+    }
+}
+
+impl<E> Socket for Stream<E>
+where
+    E: Endpoints,
+{
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> Result<usize, ReplyError> {
+        while self.pending_pos == self.pending.len() {
+            let len = self.endpoints.max_packet_size() * READ_PACKETS;
+            self.pending = self.endpoints.receive(len).await?;
+            self.pending_pos = 0;
+        }
+
+        let pending = &self.pending[self.pending_pos..];
+        let n = pending.len().min(buf.len());
+        buf[..n].copy_from_slice(&pending[..n]);
+        self.pending_pos += n;
+
+        Ok(n)
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> Result<(), ReplyError> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        self.endpoints.send(buf.to_vec()).await?;
+        if buf.len() % self.endpoints.max_packet_size() == 0 {
+            self.endpoints.send(Vec::new()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+// The number of packets requested in each IN transfer.
+const READ_PACKETS: usize = 32;
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, io};
+
+    use futures_executor::block_on;
+    use futures_util::{
+        future::{join, select, Either},
+        pin_mut,
+    };
+    use serde::{Deserialize, Serialize};
+    use zlink::{
+        connection::{Channel, Endpoint},
+        mux::Multiplexer,
+    };
+
+    use super::*;
+    use crate::Connection;
+
+    #[test]
+    fn framing() {
+        block_on(async {
+            let mut messages = Vec::new();
+            for text in ["hello", "a longer message spanning packets"] {
+                messages.extend(
+                    format!(
+                        r#"{{"method":"org.example.echo.Echo","parameters":{{"text":"{text}"}}}}"#
+                    )
+                    .bytes(),
+                );
+                messages.push(b'\0');
+            }
+            // Split the messages into packets, with zero-length packets in between.
+            let mut incoming = VecDeque::new();
+            for packet in messages.chunks(PACKET_SIZE) {
+                incoming.push_back(packet.to_vec());
+                incoming.push_back(Vec::new());
+            }
+            let endpoints = MockEndpoints {
+                incoming,
+                outgoing: Vec::new(),
+            };
+            let mut connection: Connection<MockEndpoints> = Connection::new(Stream::new(endpoints));
+
+            for expected in ["hello", "a longer message spanning packets"] {
+                let call = connection
+                    .receive_call::<Method<'_>, &'static str>()
+                    .await
+                    .unwrap();
+                let Method::Echo { text } = call.into_method();
+                assert_eq!(text, expected);
+            }
+
+            // A zero-length packet follows transfers that are a multiple of the packet size.
+            let mut stream = Stream::new(MockEndpoints {
+                incoming: VecDeque::new(),
+                outgoing: Vec::new(),
+            });
+            stream
+                .write::<&'static str>(&[0; 2 * PACKET_SIZE])
+                .await
+                .unwrap();
+            stream
+                .write::<&'static str>(&[0; PACKET_SIZE + 1])
+                .await
+                .unwrap();
+            let outgoing = &stream.endpoints().outgoing;
+            assert_eq!(outgoing.len(), 3);
+            assert_eq!(outgoing[0].len(), 2 * PACKET_SIZE);
+            assert!(outgoing[1].is_empty());
+            assert_eq!(outgoing[2].len(), PACKET_SIZE + 1);
+        })
+    }
+
+    #[test]
+    fn multiplexed() {
+        // One channel for each direction, standing in for the USB endpoints.
+        let (to_host, to_device) = (Channel::new(), Channel::new());
+        let (device_writer, host_reader) = to_host.split();
+        let (host_writer, device_reader) = to_device.split();
+        // The host side, as created by `connect_multiplexed`.
+        let host = Multiplexer::<_, 2>::new(Stream::new(PipeEndpoints(host_writer)));
+        let host_reader = Stream::new(PipeEndpoints(host_reader));
+        // The device side, as with `zlink-micro`.
+        let device = Multiplexer::<_, 2>::new(device_writer);
+
+        let run = join(
+            host.run::<_, &'static str>(host_reader),
+            device.run::<_, &'static str>(device_reader),
+        );
+        let test = async {
+            let mut host1 = host.connection(1).unwrap();
+            let mut device1 = device.connection(1).unwrap();
+            let serve = async {
+                let call = device1
+                    .receive_call::<Method<'_>, &'static str>()
+                    .await
+                    .unwrap();
+                let Method::Echo { text } = call.into_method();
+                let text = text.to_owned();
+                device1
+                    .send_reply::<_, &'static str>(Some(Echoed { text }), None)
+                    .await
+                    .unwrap();
+            };
+            let call = async {
+                let reply = host1
+                    .call_method::<_, Echoed, EchoError>(Method::Echo {
+                        text: "a message spanning several packets",
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    reply.into_parameters().unwrap().text,
+                    "a message spanning several packets"
+                );
+            };
+            join(serve, call).await;
+
+            // Closing the connection on the host ends it on the device.
+            drop(host1);
+            assert!(device1
+                .receive_call::<Method<'_>, &'static str>()
+                .await
+                .is_err());
+        };
+
+        block_on(async {
+            pin_mut!(run, test);
+            if let Either::Left(((host, device), _)) = select(run, test).await {
+                panic!("multiplexer exited: {host:?}, {device:?}");
+            }
+        });
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "method", content = "parameters")]
+    enum Method<'a> {
+        #[serde(rename = "org.example.echo.Echo")]
+        Echo { text: &'a str },
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Echoed {
+        text: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(tag = "error", content = "parameters")]
+    enum EchoError {
+        #[serde(rename = "org.example.echo.Failed")]
+        Failed,
+    }
+
+    const PACKET_SIZE: usize = 8;
+
+    #[derive(Debug)]
+    struct MockEndpoints {
+        incoming: VecDeque<Vec<u8>>,
+        outgoing: Vec<Vec<u8>>,
+    }
+
+    impl Endpoints for MockEndpoints {
+        async fn receive(&mut self, len: usize) -> io::Result<Vec<u8>> {
+            assert_eq!(len % PACKET_SIZE, 0);
+
+            self.incoming
+                .pop_front()
+                .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+        }
+
+        async fn send(&mut self, data: Vec<u8>) -> io::Result<()> {
+            self.outgoing.push(data);
+
+            Ok(())
+        }
+
+        fn max_packet_size(&self) -> usize {
+            PACKET_SIZE
+        }
+    }
+
+    // Endpoints over a channel endpoint, only used for one direction.
+    #[derive(Debug)]
+    struct PipeEndpoints<'c>(Endpoint<'c>);
+
+    impl Endpoints for PipeEndpoints<'_> {
+        async fn receive(&mut self, len: usize) -> io::Result<Vec<u8>> {
+            let mut buf = vec![0; len];
+            let n = self
+                .0
+                .read::<&'static str>(&mut buf)
+                .await
+                .map_err(|_| io::Error::other("channel read failed"))?;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            buf.truncate(n);
+
+            Ok(buf)
+        }
+
+        async fn send(&mut self, data: Vec<u8>) -> io::Result<()> {
+            self.0
+                .write::<&'static str>(&data)
+                .await
+                .map_err(|_| io::Error::other("channel write failed"))
+        }
+
+        fn max_packet_size(&self) -> usize {
+            PACKET_SIZE
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Contains the Varlink address type.
+//!
+//! Varlink addresses are URIs of the form `<transport>:<location>[;<name>=<value>...]`:
+//!
+//! * `unix:/run/org.example.service`: a Unix Domain Socket at the given path.
+//! * `unix:@org.example.service`: an abstract Unix Domain Socket with the given name.
+//! * `tcp:127.0.0.1:12345`: a TCP socket. IPv6 hosts are written in brackets, e.g `tcp:[::1]:80`.
+//! * `exec:/usr/libexec/org.example.service`: a service executable to spawn.
+//!
+//! The parameters are transport-specific, e.g `unix:/run/org.example.service;mode=0600` sets the
+//! permissions of the socket file when listening on it, with `zlink_tokio::bind`.
+
+use core::fmt;
+
+/// A Varlink address.
+///
+/// The address borrows the string it's parsed from. Its [`Display`](fmt::Display) implementation
+/// formats it back into its URI form.
+///
+/// ```
+/// use zlink::address::{Address, Transport};
+///
+/// let address = Address::parse("unix:/run/org.example.ftl;mode=0600").unwrap();
+/// assert_eq!(address.transport(), Transport::Unix("/run/org.example.ftl"));
+/// assert_eq!(address.mode(), Some(0o600));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Address<'a> {
+    transport: Transport<'a>,
+    parameters: &'a str,
+}
+
+impl<'a> Address<'a> {
+    /// Create a new address without any parameters.
+    pub fn new(transport: Transport<'a>) -> Self {
+        Self {
+            transport,
+            parameters: "",
+        }
+    }
+
+    /// Parse an address from its URI form.
+    pub fn parse(address: &'a str) -> Result<Self, Error> {
+        let (address, parameters) = address.split_once(';').unwrap_or((address, ""));
+        let (transport, location) = address.split_once(':').ok_or(Error::MissingTransport)?;
+        if location.is_empty() {
+            return Err(Error::MissingLocation);
+        }
+        let transport = match transport {
+            "unix" => match location.strip_prefix('@') {
+                Some("") => return Err(Error::MissingLocation),
+                Some(name) => Transport::UnixAbstract(name),
+                None => Transport::Unix(location),
+            },
+            "tcp" => {
+                let (host, port) = location.rsplit_once(':').ok_or(Error::InvalidPort)?;
+                let port = port.parse().map_err(|_| Error::InvalidPort)?;
+                let host = match host.strip_prefix('[') {
+                    Some(host) => host.strip_suffix(']').ok_or(Error::InvalidHost)?,
+                    None if host.contains(':') => return Err(Error::InvalidHost),
+                    None => host,
+                };
+                if host.is_empty() {
+                    return Err(Error::InvalidHost);
+                }
+
+                Transport::Tcp { host, port }
+            }
+            "exec" => Transport::Exec(location),
+            _ => return Err(Error::UnknownTransport),
+        };
+
+        let address = Self {
+            transport,
+            parameters,
+        };
+        for (name, value) in address.parameters() {
+            if name.is_empty() || (name == "mode" && parse_mode(value).is_none()) {
+                return Err(Error::InvalidParameter);
+            }
+        }
+
+        Ok(address)
+    }
+
+    /// The transport and its location.
+    pub fn transport(&self) -> Transport<'a> {
+        self.transport
+    }
+
+    /// The parameters of the address, as name and value pairs.
+    ///
+    /// The value is empty for parameters without one.
+    pub fn parameters(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.parameters
+            .split(';')
+            .filter(|parameter| !parameter.is_empty())
+            .map(|parameter| parameter.split_once('=').unwrap_or((parameter, "")))
+    }
+
+    /// The value of the parameter `name`, if present.
+    pub fn parameter(&self, name: &str) -> Option<&'a str> {
+        self.parameters()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The file permissions set through the `mode` parameter, if present.
+    pub fn mode(&self) -> Option<u32> {
+        self.parameter("mode").and_then(parse_mode)
+    }
+}
+
+impl fmt::Display for Address<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.transport {
+            Transport::Unix(path) => write!(f, "unix:{path}")?,
+            Transport::UnixAbstract(name) => write!(f, "unix:@{name}")?,
+            Transport::Tcp { host, port } if host.contains(':') => {
+                write!(f, "tcp:[{host}]:{port}")?
+            }
+            Transport::Tcp { host, port } => write!(f, "tcp:{host}:{port}")?,
+            Transport::Exec(path) => write!(f, "exec:{path}")?,
+        }
+        if !self.parameters.is_empty() {
+            write!(f, ";{}", self.parameters)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Address<'a> {
+    type Error = Error;
+
+    fn try_from(address: &'a str) -> Result<Self, Self::Error> {
+        Self::parse(address)
+    }
+}
+
+impl<'a> From<Transport<'a>> for Address<'a> {
+    fn from(transport: Transport<'a>) -> Self {
+        Self::new(transport)
+    }
+}
+
+/// The transport of an [`Address`], along with its location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport<'a> {
+    /// A Unix Domain Socket at the given path.
+    Unix(&'a str),
+    /// An abstract Unix Domain Socket with the given name, without the leading `@`.
+    UnixAbstract(&'a str),
+    /// A TCP socket.
+    Tcp {
+        /// The host name or IP address, without the brackets of IPv6 addresses.
+        host: &'a str,
+        /// The port.
+        port: u16,
+    },
+    /// An executable to spawn, serving the connection.
+    Exec(&'a str),
+}
+
+/// Errors parsing an [`Address`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The address has no transport.
+    MissingTransport,
+    /// The transport is not known.
+    UnknownTransport,
+    /// The address has no location, e.g `unix:`.
+    MissingLocation,
+    /// The host of a TCP address is invalid.
+    InvalidHost,
+    /// The port of a TCP address is missing or invalid.
+    InvalidPort,
+    /// A parameter is invalid.
+    InvalidParameter,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingTransport => write!(f, "missing transport"),
+            Error::UnknownTransport => write!(f, "unknown transport"),
+            Error::MissingLocation => write!(f, "missing location"),
+            Error::InvalidHost => write!(f, "invalid host"),
+            Error::InvalidPort => write!(f, "missing or invalid port"),
+            Error::InvalidParameter => write!(f, "invalid parameter"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+// Parse the value of a `mode` parameter, in octal.
+fn parse_mode(mode: &str) -> Option<u32> {
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for (uri, transport) in [
+            (
+                "unix:/run/org.example.ftl",
+                Transport::Unix("/run/org.example.ftl"),
+            ),
+            (
+                "unix:@org.example.ftl",
+                Transport::UnixAbstract("org.example.ftl"),
+            ),
+            (
+                "tcp:127.0.0.1:12345",
+                Transport::Tcp {
+                    host: "127.0.0.1",
+                    port: 12345,
+                },
+            ),
+            (
+                "tcp:[::1]:80",
+                Transport::Tcp {
+                    host: "::1",
+                    port: 80,
+                },
+            ),
+            ("exec:/usr/libexec/ftl", Transport::Exec("/usr/libexec/ftl")),
+        ] {
+            let address = Address::parse(uri).unwrap();
+            assert_eq!(address.transport(), transport);
+            assert_eq!(address.parameters().count(), 0);
+            assert_eq!(address.to_string(), uri);
+        }
+
+        let uri = "unix:/run/org.example.ftl;mode=0660;foo";
+        let address = Address::parse(uri).unwrap();
+        assert_eq!(address.mode(), Some(0o660));
+        assert_eq!(address.parameter("foo"), Some(""));
+        assert_eq!(address.parameter("bar"), None);
+        assert_eq!(address.to_string(), uri);
+
+        for (uri, error) in [
+            ("/run/org.example.ftl", Error::MissingTransport),
+            ("udp:127.0.0.1:53", Error::UnknownTransport),
+            ("unix:", Error::MissingLocation),
+            ("unix:@", Error::MissingLocation),
+            ("tcp:localhost", Error::InvalidPort),
+            ("tcp:localhost:http", Error::InvalidPort),
+            ("tcp::80", Error::InvalidHost),
+            ("tcp:::1:80", Error::InvalidHost),
+            ("unix:/run/org.example.ftl;mode=rw", Error::InvalidParameter),
+        ] {
+            assert_eq!(Address::parse(uri), Err(error), "{uri}");
+        }
+    }
+}
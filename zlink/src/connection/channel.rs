@@ -0,0 +1,372 @@
+use core::{
+    cell::RefCell,
+    future::poll_fn,
+    num::NonZeroUsize,
+    task::{Poll, Waker},
+};
+
+use super::Socket;
+
+/// An in-memory channel, connecting two [`Endpoint`]s.
+///
+/// This is mainly useful for testing services without any actual I/O. The endpoints borrow the
+/// channel, which doesn't allocate, so this works without `std` as well. The endpoints aren't
+/// `Send` so both must be used from the same thread.
+///
+/// ```
+/// use zlink::{connection::Channel, Connection};
+///
+/// let channel = Channel::new();
+/// let (client, server) = channel.split();
+/// let (client, server) = (Connection::new(client), Connection::new(server));
+/// ```
+#[derive(Debug)]
+pub struct Channel {
+    inner: RefCell<Inner>,
+}
+
+impl Channel {
+    /// Create a new channel.
+    pub fn new() -> Self {
+        Self {
+            inner: RefCell::new(Inner {
+                pipes: [Pipe::new(), Pipe::new()],
+                max_read_size: None,
+            }),
+        }
+    }
+
+    /// Split the channel into its two connected endpoints.
+    ///
+    /// Whatever is written to one of the endpoints can be read from the other.
+    pub fn split(&self) -> (Endpoint<'_>, Endpoint<'_>) {
+        (
+            Endpoint {
+                channel: self,
+                side: 0,
+            },
+            Endpoint {
+                channel: self,
+                side: 1,
+            },
+        )
+    }
+
+    /// Limit the number of bytes returned by each read from the endpoints.
+    ///
+    /// By default, reads return as much data as available. Limiting it fragments the messages
+    /// across multiple reads, as can happen with actual sockets.
+    pub fn set_max_read_size(&self, max: Option<NonZeroUsize>) {
+        self.inner.borrow_mut().max_read_size = max;
+    }
+
+    /// The maximum number of bytes returned by each read from the endpoints.
+    pub fn max_read_size(&self) -> Option<NonZeroUsize> {
+        self.inner.borrow().max_read_size
+    }
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An endpoint of a [`Channel`].
+///
+/// Use [`Channel::split`] to get one. Dropping it closes both directions of the channel.
+#[derive(Debug)]
+pub struct Endpoint<'c> {
+    channel: &'c Channel,
+    // Index of the pipe this endpoint writes to. It reads from the other one.
+    side: usize,
+}
+
+impl Socket for Endpoint<'_> {
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> crate::Result<usize, ReplyError> {
+        poll_fn(|cx| {
+            let mut inner = self.channel.inner.borrow_mut();
+            let max = inner.max_read_size.map_or(usize::MAX, NonZeroUsize::get);
+            let pipe = &mut inner.pipes[1 - self.side];
+            if pipe.len == 0 {
+                if pipe.closed {
+                    return Poll::Ready(Ok(0));
+                }
+                pipe.reader = Some(cx.waker().clone());
+
+                return Poll::Pending;
+            }
+
+            let n = pipe.len.min(buf.len()).min(max);
+            buf[..n].copy_from_slice(&pipe.buffer[..n]);
+            pipe.buffer.copy_within(n..pipe.len, 0);
+            pipe.len -= n;
+            let writer = pipe.writer.take();
+            // Release the borrow first, in case waking polls the writer right away.
+            drop(inner);
+            if let Some(writer) = writer {
+                writer.wake();
+            }
+
+            Poll::Ready(Ok(n))
+        })
+        .await
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> crate::Result<(), ReplyError> {
+        let mut pos = 0;
+
+        while pos < buf.len() {
+            let n = poll_fn(|cx| {
+                let mut inner = self.channel.inner.borrow_mut();
+                let pipe = &mut inner.pipes[self.side];
+                if pipe.closed {
+                    #[cfg(not(feature = "std"))]
+                    return Poll::Ready(Err(crate::Error::SocketWrite));
+                    #[cfg(feature = "std")]
+                    return Poll::Ready(Err(crate::Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::BrokenPipe,
+                        "the other endpoint was dropped",
+                    ))));
+                }
+                let n = (CAPACITY - pipe.len).min(buf.len() - pos);
+                if n == 0 {
+                    pipe.writer = Some(cx.waker().clone());
+
+                    return Poll::Pending;
+                }
+
+                pipe.buffer[pipe.len..pipe.len + n].copy_from_slice(&buf[pos..pos + n]);
+                pipe.len += n;
+                let reader = pipe.reader.take();
+                drop(inner);
+                if let Some(reader) = reader {
+                    reader.wake();
+                }
+
+                Poll::Ready(Ok(n))
+            })
+            .await?;
+            pos += n;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Endpoint<'_> {
+    fn drop(&mut self) {
+        let mut wakers = [None, None, None, None];
+        {
+            let mut inner = self.channel.inner.borrow_mut();
+            for (pipe, wakers) in inner.pipes.iter_mut().zip(wakers.chunks_mut(2)) {
+                pipe.closed = true;
+                wakers[0] = pipe.reader.take();
+                wakers[1] = pipe.writer.take();
+            }
+        }
+        for waker in wakers.into_iter().flatten() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    pipes: [Pipe; 2],
+    max_read_size: Option<NonZeroUsize>,
+}
+
+// One direction of the channel.
+#[derive(Debug)]
+struct Pipe {
+    buffer: [u8; CAPACITY],
+    len: usize,
+    // If any of the endpoints was dropped.
+    closed: bool,
+    reader: Option<Waker>,
+    writer: Option<Waker>,
+}
+
+impl Pipe {
+    fn new() -> Self {
+        Self {
+            buffer: [0; CAPACITY],
+            len: 0,
+            closed: false,
+            reader: None,
+            writer: None,
+        }
+    }
+}
+
+// The number of bytes that can be written before the other endpoint reads them.
+const CAPACITY: usize = 4 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+    use futures_util::future::join;
+
+    use super::*;
+
+    #[test]
+    fn read_write() {
+        block_on(async {
+            let channel = Channel::new();
+            let (mut left, mut right) = channel.split();
+
+            // More than the capacity, so that the writer has to wait for the reader.
+            let data = [0x42; CAPACITY * 2 + 1];
+            let write = left.write::<&'static str>(&data);
+            let read = async {
+                let mut received = 0;
+                let mut buf = [0; 1024];
+                while received < data.len() {
+                    let n = right.read::<&'static str>(&mut buf).await.unwrap();
+                    assert!(buf[..n].iter().all(|b| *b == 0x42));
+                    received += n;
+                }
+            };
+            let (res, ()) = join(write, read).await;
+            res.unwrap();
+
+            channel.set_max_read_size(NonZeroUsize::new(3));
+            right.write::<&'static str>(b"hello").await.unwrap();
+            let mut buf = [0; 8];
+            assert_eq!(left.read::<&'static str>(&mut buf).await.unwrap(), 3);
+            assert_eq!(&buf[..3], b"hel");
+            assert_eq!(left.read::<&'static str>(&mut buf).await.unwrap(), 2);
+            assert_eq!(&buf[..2], b"lo");
+
+            drop(right);
+            assert_eq!(left.read::<&'static str>(&mut buf).await.unwrap(), 0);
+            assert!(left.write::<&'static str>(b"hello").await.is_err());
+        })
+    }
+
+    // Runs without `std` as well, only the client side going through a `Connection`.
+    #[test]
+    fn client() {
+        use serde::{Deserialize, Serialize};
+
+        use crate::Connection;
+
+        #[derive(Debug, Serialize)]
+        #[serde(tag = "method", content = "parameters")]
+        enum Method {
+            #[serde(rename = "org.example.math.Add")]
+            Add { a: i64, b: i64 },
+            #[serde(rename = "org.example.math.Count")]
+            Count { to: i64 },
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Sum {
+            sum: i64,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct MathError<'e> {
+            error: &'e str,
+        }
+
+        block_on(async {
+            let channel = Channel::new();
+            let (client, mut server) = channel.split();
+            let mut client = Connection::new(client);
+
+            let call = async {
+                let reply = client
+                    .call_method::<_, Sum, MathError<'_>>(Method::Add { a: 1, b: 2 })
+                    .await
+                    .unwrap();
+                assert_eq!(reply.parameters().unwrap().sum, 3);
+
+                let error = client
+                    .call_method::<_, Sum, MathError<'_>>(Method::Add { a: i64::MAX, b: 1 })
+                    .await
+                    .unwrap_err();
+                assert!(matches!(
+                    error,
+                    crate::Error::Reply(MathError {
+                        error: "org.example.math.Overflow"
+                    })
+                ));
+
+                let mut replies = client
+                    .call_method_more::<_, MathError<'_>>(Method::Count { to: 2 })
+                    .await
+                    .unwrap();
+                for sum in [1, 2] {
+                    let reply = replies.next::<Sum, MathError<'_>>().await.unwrap().unwrap();
+                    assert_eq!(reply.parameters().unwrap().sum, sum);
+                }
+                assert!(replies.next::<Sum, MathError<'_>>().await.is_none());
+            };
+            let serve = async {
+                let exchanges: [(&[u8], &[u8]); 3] = [
+                    (
+                        b"{\"method\":\"org.example.math.Add\",\"parameters\":{\"a\":1,\"b\":2}}\0",
+                        b"{\"parameters\":{\"sum\":3}}\0",
+                    ),
+                    (
+                        b"{\"method\":\"org.example.math.Add\",\"parameters\":{\"a\":9223372036854775807,\"b\":1}}\0",
+                        b"{\"error\":\"org.example.math.Overflow\"}\0",
+                    ),
+                    (
+                        b"{\"method\":\"org.example.math.Count\",\"parameters\":{\"to\":2},\"more\":true}\0",
+                        b"{\"parameters\":{\"sum\":1},\"continues\":true}\0{\"parameters\":{\"sum\":2}}\0",
+                    ),
+                ];
+                for (call, replies) in exchanges {
+                    let mut buf = [0; 128];
+                    let mut len = 0;
+                    while !buf[..len].ends_with(b"\0") {
+                        len += server.read::<&'static str>(&mut buf[len..]).await.unwrap();
+                    }
+                    assert_eq!(&buf[..len], call);
+                    server.write::<&'static str>(replies).await.unwrap();
+                }
+            };
+            join(call, serve).await;
+        })
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn fragmented_messages() {
+        use serde::{Deserialize, Serialize};
+
+        use crate::Connection;
+
+        #[derive(Debug, Serialize, Deserialize)]
+        #[serde(tag = "method", content = "parameters")]
+        enum Method<'a> {
+            #[serde(rename = "org.example.echo.Echo")]
+            Echo { text: &'a str },
+        }
+
+        block_on(async {
+            let channel = Channel::new();
+            channel.set_max_read_size(NonZeroUsize::new(1));
+            let (client, server) = channel.split();
+            let (mut client, mut server) = (Connection::new(client), Connection::new(server));
+
+            for text in ["hello", "world"] {
+                client
+                    .send_call::<_, &'static str>(Method::Echo { text }, None, None, None)
+                    .await
+                    .unwrap();
+            }
+            for expected in ["hello", "world"] {
+                let call = server
+                    .receive_call::<Method<'_>, &'static str>()
+                    .await
+                    .unwrap();
+                let Method::Echo { text } = call.into_method();
+                assert_eq!(text, expected);
+            }
+        })
+    }
+}
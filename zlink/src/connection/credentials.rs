@@ -0,0 +1,48 @@
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+
+/// The credentials of the peer process of a connection.
+///
+/// See [`Connection::peer_credentials`](super::Connection::peer_credentials).
+#[derive(Debug)]
+pub struct Credentials {
+    uid: u32,
+    gid: u32,
+    pid: Option<i32>,
+    pidfd: Option<OwnedFd>,
+}
+
+impl Credentials {
+    /// Create new credentials.
+    pub fn new(uid: u32, gid: u32, pid: Option<i32>, pidfd: Option<OwnedFd>) -> Self {
+        Self {
+            uid,
+            gid,
+            pid,
+            pidfd,
+        }
+    }
+
+    /// The effective user ID of the peer process.
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// The effective group ID of the peer process.
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// The process ID of the peer process, if available.
+    pub fn pid(&self) -> Option<i32> {
+        self.pid
+    }
+
+    /// A pidfd referring to the peer process, if available.
+    ///
+    /// Unlike the process ID, a pidfd keeps referring to the same process, even after it exits.
+    /// The `zlink-tokio` and `zlink-smol` sockets only provide it on Linux 6.5 and later, where the
+    /// kernel hands it out along with the credentials.
+    pub fn pidfd(&self) -> Option<BorrowedFd<'_>> {
+        self.pidfd.as_ref().map(AsFd::as_fd)
+    }
+}
@@ -0,0 +1,151 @@
+use core::{future::poll_fn, pin::Pin};
+
+use ::futures_io::{AsyncRead, AsyncWrite};
+
+use super::Socket;
+
+/// A [`Socket`] implementation for any [`futures_io`](::futures_io) I/O type.
+///
+/// This allows using any type implementing both [`AsyncRead`] and [`AsyncWrite`] as a transport,
+/// e.g pipes, TLS streams or serial ports. The data is flushed after each write.
+#[derive(Debug)]
+pub struct FuturesIo<T>(T);
+
+impl<T> FuturesIo<T> {
+    /// Create a new socket wrapping `io`.
+    pub fn new(io: T) -> Self {
+        Self(io)
+    }
+
+    /// The wrapped I/O type.
+    pub fn get_ref(&self) -> &T {
+        &self.0
+    }
+
+    /// The wrapped I/O type, mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    /// Consume the socket and return the wrapped I/O type.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Socket for FuturesIo<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> crate::Result<usize, ReplyError> {
+        poll_fn(|cx| Pin::new(&mut self.0).poll_read(cx, buf))
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn write<ReplyError>(&mut self, buf: &[u8]) -> crate::Result<(), ReplyError> {
+        let mut pos = 0;
+
+        while pos < buf.len() {
+            let n = poll_fn(|cx| Pin::new(&mut self.0).poll_write(cx, &buf[pos..])).await?;
+            if n == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+            }
+            pos += n;
+        }
+
+        poll_fn(|cx| Pin::new(&mut self.0).poll_flush(cx))
+            .await
+            .map_err(Into::into)
+    }
+}
+
+impl<T> From<T> for FuturesIo<T> {
+    fn from(io: T) -> Self {
+        Self(io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use std::{collections::VecDeque, io};
+
+    use futures_executor::block_on;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::Connection;
+
+    #[test]
+    fn loopback() {
+        block_on(async {
+            let mut connection = Connection::new(FuturesIo::new(Loopback::default()));
+            connection
+                .send_call::<_, &'static str>(Method::Echo { text: "hello" }, None, None, None)
+                .await
+                .unwrap();
+            assert!(connection.socket().get_ref().flushed);
+
+            let call = connection
+                .receive_call::<Method<'_>, &'static str>()
+                .await
+                .unwrap();
+            let Method::Echo { text } = call.into_method();
+            assert_eq!(text, "hello");
+        })
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "method", content = "parameters")]
+    enum Method<'a> {
+        #[serde(rename = "org.example.echo.Echo")]
+        Echo { text: &'a str },
+    }
+
+    // Reads back what's written to it, one byte at a time.
+    #[derive(Debug, Default)]
+    struct Loopback {
+        buffer: VecDeque<u8>,
+        flushed: bool,
+    }
+
+    impl AsyncRead for Loopback {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            match (self.buffer.pop_front(), buf.first_mut()) {
+                (Some(byte), Some(first)) => {
+                    *first = byte;
+                    Poll::Ready(Ok(1))
+                }
+                _ => Poll::Ready(Ok(0)),
+            }
+        }
+    }
+
+    impl AsyncWrite for Loopback {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.buffer.extend(buf);
+            self.flushed = false;
+
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.flushed = true;
+
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
@@ -1,7 +1,24 @@
 //! Contains connection related API.
 
+mod channel;
+pub use channel::{Channel, Endpoint};
+#[cfg(all(feature = "std", unix))]
+mod credentials;
+#[cfg(all(feature = "std", unix))]
+pub use credentials::Credentials;
+#[cfg(feature = "futures-io")]
+mod futures_io;
+#[cfg(feature = "futures-io")]
+pub use futures_io::FuturesIo;
+mod reply_stream;
+pub use reply_stream::ReplyStream;
 mod socket;
 use core::fmt::Debug;
+#[cfg(all(feature = "std", unix))]
+use std::{
+    os::fd::{BorrowedFd, OwnedFd},
+    sync::Arc,
+};
 
 use mayheap::Vec;
 use memchr::memchr;
@@ -15,6 +32,15 @@ pub use socket::Socket;
 pub struct Connection<S: Socket> {
     socket: S,
     read_pos: usize,
+    // If the read buffer contains messages that haven't been received yet.
+    messages_pending: bool,
+    // The file descriptors received along with the messages in the read buffer, with the offset of
+    // the message they were passed with.
+    #[cfg(all(feature = "std", unix))]
+    read_fds: std::collections::VecDeque<(usize, std::vec::Vec<OwnedFd>)>,
+    // The result of fetching the peer credentials, once fetched.
+    #[cfg(all(feature = "std", unix))]
+    peer_credentials: Option<Result<Arc<Credentials>, std::io::ErrorKind>>,
 
     write_buffer: Vec<u8, BUFFER_SIZE>,
     read_buffer: Vec<u8, BUFFER_SIZE>,
@@ -26,11 +52,21 @@ impl<S: Socket> Connection<S> {
         Self {
             socket,
             read_pos: 0,
+            messages_pending: false,
+            #[cfg(all(feature = "std", unix))]
+            read_fds: std::collections::VecDeque::new(),
+            #[cfg(all(feature = "std", unix))]
+            peer_credentials: None,
             write_buffer: Vec::from_slice(&[0; BUFFER_SIZE]).unwrap(),
             read_buffer: Vec::from_slice(&[0; BUFFER_SIZE]).unwrap(),
         }
     }
 
+    /// The socket of the connection.
+    pub fn socket(&self) -> &S {
+        &self.socket
+    }
+
     /// Sends a method call.
     ///
     /// The generic `Method` is the type of the method name and its input parameters. This should be
@@ -47,7 +83,11 @@ impl<S: Socket> Connection<S> {
     ///    // The name needs to be the fully-qualified name of the error.
     ///    #[serde(rename = "org.example.ftl.Alpha")]
     ///    Alpha { param1: u32, param2: &'m str},
-    ///    #[serde(rename = "org.example.ftl.Bravo")]
+    ///    // Methods without parameters may receive empty parameters.
+    ///    #[serde(
+    ///        rename = "org.example.ftl.Bravo",
+    ///        deserialize_with = "zlink::connection::empty_parameters"
+    ///    )]
     ///    Bravo,
     ///    #[serde(rename = "org.example.ftl.Charlie")]
     ///    Charlie { param1: &'m str },
@@ -63,12 +103,7 @@ impl<S: Socket> Connection<S> {
     where
         Method: Serialize + Debug,
     {
-        let call = Call {
-            method,
-            oneway,
-            more,
-            upgrade,
-        };
+        let call = Call::new(method, oneway, more, upgrade);
         let len = to_slice(&call, &mut self.write_buffer)?;
         self.write_buffer[len] = b'\0';
 
@@ -94,12 +129,19 @@ impl<S: Socket> Connection<S> {
     ///    // The name needs to be the fully-qualified name of the error.
     ///    #[serde(rename = "org.example.ftl.Alpha")]
     ///    Alpha { param1: u32, param2: String },
-    ///    #[serde(rename = "org.example.ftl.Bravo")]
+    ///    // Errors without parameters may receive empty parameters.
+    ///    #[serde(
+    ///        rename = "org.example.ftl.Bravo",
+    ///        deserialize_with = "zlink::connection::empty_parameters"
+    ///    )]
     ///    Bravo,
     ///    #[serde(rename = "org.example.ftl.Charlie")]
     ///    Charlie { param1: String },
     /// }
     /// ```
+    ///
+    /// The standard [`org.varlink.service` errors](crate::varlink_service::Error) are returned as
+    /// [`crate::Error::VarlinkService`] if `ReplyError` fails to deserialize them.
     pub async fn receive_reply<'r, Params, ReplyError>(
         &'r mut self,
     ) -> crate::Result<Reply<Params>, ReplyError>
@@ -107,16 +149,73 @@ impl<S: Socket> Connection<S> {
         Params: Deserialize<'r>,
         ReplyError: Deserialize<'r>,
     {
-        let buffer = self.read_message_bytes().await?;
-
-        // First try to parse it as an error.
-        // FIXME: This will mean the document will be parsed twice. We should instead try to
-        // quickly check if `error` field is present and then parse to the appropriate type based on
-        // that information. Perhaps a simple parser using `winnow`?
-        match from_slice::<ReplyError, ReplyError>(buffer) {
-            Ok(e) => Err(crate::Error::Reply(e)),
-            Err(_) => from_slice::<Reply<_>, _>(buffer),
+        let message = self.read_message().await?;
+        let buffer = message.bytes;
+
+        // First try to parse it as an error, starting with the service-specific ones.
+        // FIXME: This will mean the document will be parsed up to three times. We should instead
+        // try to quickly check if `error` field is present and then parse to the appropriate type
+        // based on that information. Perhaps a simple parser using `winnow`?
+        if let Ok(e) = from_slice::<ReplyError, ReplyError>(buffer) {
+            return Err(crate::Error::Reply(e));
+        }
+        if let Ok(e) = from_slice::<crate::varlink_service::Error, ReplyError>(buffer) {
+            return Err(crate::Error::VarlinkService(e));
         }
+
+        let reply = from_slice::<Reply<_>, _>(buffer)?;
+        #[cfg(all(feature = "std", unix))]
+        let reply = {
+            let mut reply = reply;
+            reply.set_fds(message.fds);
+            reply
+        };
+
+        Ok(reply)
+    }
+
+    /// Call a method and receive its reply.
+    ///
+    /// This is a convenience method that calls [`Connection::send_call`] with no flags set,
+    /// followed by [`Connection::receive_reply`]. See the documentation of those methods for
+    /// details on the generic parameters.
+    pub async fn call_method<'r, Method, Params, ReplyError>(
+        &'r mut self,
+        method: Method,
+    ) -> crate::Result<Reply<Params>, ReplyError>
+    where
+        Method: Serialize + Debug,
+        Params: Deserialize<'r>,
+        ReplyError: Deserialize<'r>,
+    {
+        self.send_call(method, None, None, None).await?;
+
+        self.receive_reply().await
+    }
+
+    /// Call a method that sends back multiple replies.
+    ///
+    /// This sends the method call with the `more` flag set and returns a [`ReplyStream`] to receive
+    /// the replies with. See [`Connection::send_call`] for details on the `Method` generic
+    /// parameter.
+    pub async fn call_method_more<Method, ReplyError>(
+        &mut self,
+        method: Method,
+    ) -> crate::Result<ReplyStream<'_, S>, ReplyError>
+    where
+        Method: Serialize + Debug,
+    {
+        self.send_call(method, None, Some(true), None).await?;
+
+        Ok(self.receive_replies())
+    }
+
+    /// Receive the replies to a method call that was sent with the `more` flag set.
+    ///
+    /// Unlike [`Connection::receive_reply`], this doesn't read anything from the socket by itself.
+    /// The replies are received through the returned [`ReplyStream`].
+    pub fn receive_replies(&mut self) -> ReplyStream<'_, S> {
+        ReplyStream::new(self)
     }
 
     /// Receive a method call over the socket.
@@ -132,9 +231,16 @@ impl<S: Socket> Connection<S> {
     where
         Method: Deserialize<'m>,
     {
-        let buffer = self.read_message_bytes().await?;
+        let message = self.read_message().await?;
+        let call = from_slice::<Call<Method>, _>(message.bytes)?;
+        #[cfg(all(feature = "std", unix))]
+        let call = {
+            let mut call = call;
+            call.set_fds(message.fds);
+            call
+        };
 
-        from_slice::<Call<Method>, _>(buffer)
+        Ok(call)
     }
 
     /// Send a reply over the socket.
@@ -149,10 +255,7 @@ impl<S: Socket> Connection<S> {
     where
         Params: Serialize + Debug,
     {
-        let reply = Reply {
-            parameters,
-            continues,
-        };
+        let reply = Reply::new(parameters, continues);
         let len = to_slice(&reply, &mut self.write_buffer)?;
         self.write_buffer[len] = b'\0';
 
@@ -172,39 +275,209 @@ impl<S: Socket> Connection<S> {
     where
         ReplyError: Serialize + Debug,
     {
-        let len = to_slice(&error, &mut self.write_buffer)?;
+        self.write_message(&error).await
+    }
+
+    /// Sends a method call, passing file descriptors along with it.
+    ///
+    /// This is the same as [`Connection::send_call`], except that `fds` are sent along with the
+    /// message. The parameters of the method call can refer to the file descriptors by their index
+    /// in `fds`, using [`FdIndex`].
+    #[cfg(all(feature = "std", unix))]
+    pub async fn send_call_with_fds<Method, ReplyError>(
+        &mut self,
+        method: Method,
+        oneway: Option<bool>,
+        more: Option<bool>,
+        upgrade: Option<bool>,
+        fds: &[BorrowedFd<'_>],
+    ) -> crate::Result<(), ReplyError>
+    where
+        Method: Serialize + Debug,
+    {
+        let call = Call::new(method, oneway, more, upgrade);
+
+        self.write_message_with_fds(&call, fds).await
+    }
+
+    /// Receives a method call reply, along with the file descriptors passed with it.
+    ///
+    /// This is the same as [`Connection::receive_reply`], except that the file descriptors
+    /// received along with the reply are also returned.
+    #[cfg(all(feature = "std", unix))]
+    pub async fn receive_reply_with_fds<'r, Params, ReplyError>(
+        &'r mut self,
+    ) -> crate::Result<(Reply<Params>, std::vec::Vec<OwnedFd>), ReplyError>
+    where
+        Params: Deserialize<'r>,
+        ReplyError: Deserialize<'r>,
+    {
+        let mut reply = self.receive_reply().await?;
+        let fds = reply.take_fds();
+
+        Ok((reply, fds))
+    }
+
+    /// Receive a method call over the socket, along with the file descriptors passed with it.
+    ///
+    /// This is the same as [`Connection::receive_call`], except that the file descriptors received
+    /// along with the method call are also returned.
+    #[cfg(all(feature = "std", unix))]
+    pub async fn receive_call_with_fds<'m, Method, ReplyError>(
+        &'m mut self,
+    ) -> crate::Result<(Call<Method>, std::vec::Vec<OwnedFd>), ReplyError>
+    where
+        Method: Deserialize<'m>,
+    {
+        let mut call = self.receive_call().await?;
+        let fds = call.take_fds();
+
+        Ok((call, fds))
+    }
+
+    /// Send a reply over the socket, passing file descriptors along with it.
+    ///
+    /// This is the same as [`Connection::send_reply`], except that `fds` are sent along with the
+    /// message. The reply parameters can refer to the file descriptors by their index in `fds`,
+    /// using [`FdIndex`].
+    #[cfg(all(feature = "std", unix))]
+    pub async fn send_reply_with_fds<Params, ReplyError>(
+        &mut self,
+        parameters: Option<Params>,
+        continues: Option<bool>,
+        fds: &[BorrowedFd<'_>],
+    ) -> crate::Result<(), ReplyError>
+    where
+        Params: Serialize + Debug,
+    {
+        let reply = Reply::new(parameters, continues);
+
+        self.write_message_with_fds(&reply, fds).await
+    }
+
+    /// The credentials of the peer process.
+    ///
+    /// The credentials are fetched from the socket the first time and then cached. If fetching
+    /// them fails, e.g because the socket doesn't support it, the kind of the error is cached
+    /// instead so the socket isn't queried again for each method call.
+    #[cfg(all(feature = "std", unix))]
+    pub fn peer_credentials<ReplyError>(&mut self) -> crate::Result<Arc<Credentials>, ReplyError> {
+        let result = match &self.peer_credentials {
+            Some(result) => result.clone(),
+            None => {
+                let result = self
+                    .socket
+                    .peer_credentials()
+                    .map(Arc::new)
+                    .map_err(|e| e.kind());
+                self.peer_credentials = Some(result.clone());
+
+                result
+            }
+        };
+
+        result.map_err(|kind| std::io::Error::from(kind).into())
+    }
+
+    /// Wait for a message to arrive, without receiving it.
+    ///
+    /// Once this returns successfully, the next [`Connection::receive_call`] or
+    /// [`Connection::receive_reply`] call will not need to wait for the message. This is useful for
+    /// waiting on incoming messages alongside other events, e.g to stop serving a connection when
+    /// idle. Cancelling the returned future discards any partially received message.
+    pub async fn wait_for_message<ReplyError>(&mut self) -> crate::Result<(), ReplyError> {
+        self.read_from_socket().await
+    }
+
+    // Writes `message` to the socket.
+    pub(crate) async fn write_message<T, ReplyError>(
+        &mut self,
+        message: &T,
+    ) -> crate::Result<(), ReplyError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let len = to_slice(message, &mut self.write_buffer)?;
         self.write_buffer[len] = b'\0';
 
         self.socket.write(&self.write_buffer[..=len]).await
     }
 
-    // Reads at least one full message from the socket and return a single message bytes.
-    async fn read_message_bytes<ReplyError>(&mut self) -> crate::Result<&'_ [u8], ReplyError> {
+    // Writes `message` to the socket, passing `fds` along with it.
+    #[cfg(all(feature = "std", unix))]
+    async fn write_message_with_fds<T, ReplyError>(
+        &mut self,
+        message: &T,
+        fds: &[BorrowedFd<'_>],
+    ) -> crate::Result<(), ReplyError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let len = to_slice(message, &mut self.write_buffer)?;
+        self.write_buffer[len] = b'\0';
+
+        self.socket
+            .write_with_fds(&self.write_buffer[..=len], fds)
+            .await
+    }
+
+    // Reads at least one full message from the socket and return a single message.
+    pub(crate) async fn read_message<ReplyError>(
+        &mut self,
+    ) -> crate::Result<Message<'_>, ReplyError> {
         self.read_from_socket().await?;
 
         // Unwrap is safe because `read_from_socket` call above ensures at least one null byte in
         // the buffer.
-        let null_index = memchr(b'\0', &self.read_buffer[self.read_pos..]).unwrap() + self.read_pos;
-        let buffer = &self.read_buffer[self.read_pos..null_index];
+        let start = self.read_pos;
+        let null_index = memchr(b'\0', &self.read_buffer[start..]).unwrap() + start;
         if self.read_buffer[null_index + 1] == b'\0' {
             // This means we're reading the last message and can now reset the index.
             self.read_pos = 0;
+            self.messages_pending = false;
         } else {
             self.read_pos = null_index + 1;
         }
 
-        Ok(buffer)
+        // Several reads may have passed file descriptors along with the same message.
+        #[cfg(all(feature = "std", unix))]
+        let mut fds = std::vec::Vec::new();
+        #[cfg(all(feature = "std", unix))]
+        while self
+            .read_fds
+            .front()
+            .is_some_and(|(offset, _)| *offset == start)
+        {
+            fds.extend(self.read_fds.pop_front().unwrap().1);
+        }
+
+        Ok(Message {
+            bytes: &self.read_buffer[start..null_index],
+            #[cfg(all(feature = "std", unix))]
+            fds,
+        })
     }
 
     // Reads at least one full message from the socket.
     async fn read_from_socket<ReplyError>(&mut self) -> crate::Result<(), ReplyError> {
-        if self.read_pos > 0 {
-            // This means we already have at least one message in the buffer so no need to read.
+        if self.messages_pending {
+            // We already have at least one message in the buffer so no need to read.
             return Ok(());
         }
+        // Only left if reading the last messages got cancelled, along with their partial data.
+        #[cfg(all(feature = "std", unix))]
+        self.read_fds.clear();
 
         let mut pos = self.read_pos;
         loop {
+            #[cfg(all(feature = "std", unix))]
+            let mut fds = std::vec::Vec::new();
+            #[cfg(all(feature = "std", unix))]
+            let bytes_read = self
+                .socket
+                .read_with_fds(&mut self.read_buffer[pos..], &mut fds)
+                .await?;
+            #[cfg(not(all(feature = "std", unix)))]
             let bytes_read = self.socket.read(&mut self.read_buffer[pos..]).await?;
             if bytes_read == 0 {
                 #[cfg(not(feature = "std"))]
@@ -222,6 +495,17 @@ impl<S: Socket> Connection<S> {
             // read all messages and can now reset the `read_pos`.
             self.read_buffer[total_read] = b'\0';
 
+            #[cfg(all(feature = "std", unix))]
+            if !fds.is_empty() {
+                let offset = fds_message_offset(
+                    &self.read_buffer,
+                    pos,
+                    total_read,
+                    cfg!(any(target_os = "linux", target_os = "android")),
+                );
+                self.read_fds.push_back((offset, fds));
+            }
+
             if self.read_buffer[total_read - 1] == b'\0' {
                 // One or more full messages were read.
                 break;
@@ -239,11 +523,35 @@ impl<S: Socket> Connection<S> {
 
             pos += bytes_read;
         }
+        self.messages_pending = true;
 
         Ok(())
     }
 }
 
+// A message read from the socket.
+#[derive(Debug)]
+pub(crate) struct Message<'b> {
+    pub(crate) bytes: &'b [u8],
+    // The file descriptors passed along with the message.
+    #[cfg(all(feature = "std", unix))]
+    pub(crate) fds: std::vec::Vec<OwnedFd>,
+}
+
+// The offset of the message that the file descriptors received by reading `buffer[start..end]`
+// were passed with.
+//
+// The data a file descriptor is passed with is never merged with later data into a single read.
+// On Linux, it may however be preceded by earlier data, so the file descriptors belong to the
+// message the last byte read is part of, which `fds_with_last_byte` tells. On other systems, the
+// read stops before it instead so they belong to the message the first byte read is part of.
+#[cfg(all(feature = "std", unix))]
+fn fds_message_offset(buffer: &[u8], start: usize, end: usize, fds_with_last_byte: bool) -> usize {
+    let byte = if fds_with_last_byte { end - 1 } else { start };
+
+    memchr::memrchr(b'\0', &buffer[..byte]).map_or(0, |i| i + 1)
+}
+
 /// A successful method call reply.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Reply<Params> {
@@ -251,9 +559,22 @@ pub struct Reply<Params> {
     parameters: Option<Params>,
     #[serde(skip_serializing_if = "Option::is_none")]
     continues: Option<bool>,
+    #[cfg(all(feature = "std", unix))]
+    #[serde(skip)]
+    fds: std::vec::Vec<OwnedFd>,
 }
 
 impl<Params> Reply<Params> {
+    /// Create a new reply.
+    pub fn new(parameters: Option<Params>, continues: Option<bool>) -> Self {
+        Self {
+            parameters,
+            continues,
+            #[cfg(all(feature = "std", unix))]
+            fds: std::vec::Vec::new(),
+        }
+    }
+
     /// The parameters of the reply.
     pub fn parameters(&self) -> Option<&Params> {
         self.parameters.as_ref()
@@ -268,6 +589,29 @@ impl<Params> Reply<Params> {
     pub fn continues(&self) -> Option<bool> {
         self.continues
     }
+
+    /// The file descriptors passed along with the reply.
+    ///
+    /// The reply parameters refer to them by their index, using [`FdIndex`].
+    #[cfg(all(feature = "std", unix))]
+    pub fn fds(&self) -> &[OwnedFd] {
+        &self.fds
+    }
+
+    /// Set the file descriptors to pass along with the reply.
+    ///
+    /// Services use this to pass file descriptors along with the replies of a
+    /// [`MethodReply::Multi`](crate::server::MethodReply::Multi) stream.
+    #[cfg(all(feature = "std", unix))]
+    pub fn set_fds(&mut self, fds: std::vec::Vec<OwnedFd>) {
+        self.fds = fds;
+    }
+
+    /// Take the file descriptors passed along with the reply, leaving none in their place.
+    #[cfg(all(feature = "std", unix))]
+    pub fn take_fds(&mut self) -> std::vec::Vec<OwnedFd> {
+        core::mem::take(&mut self.fds)
+    }
 }
 
 /// A method call.
@@ -281,14 +625,39 @@ pub struct Call<M> {
     more: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     upgrade: Option<bool>,
+    #[cfg(all(feature = "std", unix))]
+    #[serde(skip)]
+    peer_credentials: Option<Arc<Credentials>>,
+    #[cfg(all(feature = "std", unix))]
+    #[serde(skip)]
+    fds: std::vec::Vec<OwnedFd>,
 }
 
 impl<M> Call<M> {
+    /// Create a new method call.
+    pub fn new(method: M, oneway: Option<bool>, more: Option<bool>, upgrade: Option<bool>) -> Self {
+        Self {
+            method,
+            oneway,
+            more,
+            upgrade,
+            #[cfg(all(feature = "std", unix))]
+            peer_credentials: None,
+            #[cfg(all(feature = "std", unix))]
+            fds: std::vec::Vec::new(),
+        }
+    }
+
     /// The method call name and parameters.
     pub fn method(&self) -> &M {
         &self.method
     }
 
+    /// Convert the method call into its name and parameters.
+    pub fn into_method(self) -> M {
+        self.method
+    }
+
     /// If the method call doesn't want a reply.
     pub fn oneway(&self) -> Option<bool> {
         self.oneway
@@ -303,6 +672,152 @@ impl<M> Call<M> {
     pub fn upgrade(&self) -> Option<bool> {
         self.upgrade
     }
+
+    /// The credentials of the process that made the method call, if known.
+    ///
+    /// This is set by the receiver of the method call, through [`Call::set_peer_credentials`].
+    #[cfg(all(feature = "std", unix))]
+    pub fn peer_credentials(&self) -> Option<&Credentials> {
+        self.peer_credentials.as_deref()
+    }
+
+    /// Set the credentials of the process that made the method call.
+    #[cfg(all(feature = "std", unix))]
+    pub fn set_peer_credentials(&mut self, credentials: Option<Arc<Credentials>>) {
+        self.peer_credentials = credentials;
+    }
+
+    /// Take the credentials of the process that made the method call, leaving `None` in their
+    /// place.
+    ///
+    /// This is useful to keep the credentials around after converting the call into its method,
+    /// through [`Call::into_method`].
+    #[cfg(all(feature = "std", unix))]
+    pub fn take_peer_credentials(&mut self) -> Option<Arc<Credentials>> {
+        self.peer_credentials.take()
+    }
+
+    /// The file descriptors passed along with the method call.
+    ///
+    /// The method call parameters refer to them by their index, using [`FdIndex`].
+    #[cfg(all(feature = "std", unix))]
+    pub fn fds(&self) -> &[OwnedFd] {
+        &self.fds
+    }
+
+    /// Set the file descriptors passed along with the method call.
+    #[cfg(all(feature = "std", unix))]
+    pub fn set_fds(&mut self, fds: std::vec::Vec<OwnedFd>) {
+        self.fds = fds;
+    }
+
+    /// Take the file descriptors passed along with the method call, leaving none in their place.
+    ///
+    /// This is useful to keep the file descriptors around after converting the call into its
+    /// method, through [`Call::into_method`].
+    #[cfg(all(feature = "std", unix))]
+    pub fn take_fds(&mut self) -> std::vec::Vec<OwnedFd> {
+        core::mem::take(&mut self.fds)
+    }
+}
+
+/// The index of a file descriptor passed along with a message.
+///
+/// Method call and reply parameters refer to the file descriptors passed along with the message
+/// by their index, serialized as an integer. See [`Connection::send_call_with_fds`] and
+/// [`Connection::receive_call_with_fds`].
+#[cfg(all(feature = "std", unix))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FdIndex(usize);
+
+#[cfg(all(feature = "std", unix))]
+impl FdIndex {
+    /// Create a new index.
+    pub fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    /// The index.
+    pub fn get(self) -> usize {
+        self.0
+    }
+
+    /// The file descriptor this index refers to in `fds`, if any.
+    pub fn fd<F>(self, fds: &[F]) -> Option<&F> {
+        fds.get(self.0)
+    }
+}
+
+/// Deserialize the parameters of a method call or an error that has none.
+///
+/// Peers may either omit the `parameters` field or send an empty object, but `serde` only accepts
+/// the former for unit variants of adjacently tagged enums. Use this through
+/// `#[serde(deserialize_with = "...")]` on such variants to accept both:
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, Deserialize, Serialize)]
+/// #[serde(tag = "method", content = "parameters")]
+/// enum MyMethods {
+///    #[serde(
+///        rename = "org.example.ftl.Bravo",
+///        deserialize_with = "zlink::connection::empty_parameters"
+///    )]
+///    Bravo,
+/// }
+///
+/// for json in [
+///     r#"{"method":"org.example.ftl.Bravo"}"#,
+///     r#"{"method":"org.example.ftl.Bravo","parameters":{}}"#,
+/// ] {
+///     assert_eq!(serde_json::from_str::<MyMethods>(json).unwrap(), MyMethods::Bravo);
+/// }
+/// ```
+pub fn empty_parameters<'de, D>(deserializer: D) -> Result<(), D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct EmptyParameters;
+
+    impl<'de> serde::de::Visitor<'de> for EmptyParameters {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            formatter.write_str("empty parameters")
+        }
+
+        fn visit_unit<E>(self) -> Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_none<E>(self) -> Result<(), E> {
+            Ok(())
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<(), D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            deserializer.deserialize_map(self)
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            // Like for any other parameters, unknown fields are ignored.
+            while map
+                .next_entry::<serde::de::IgnoredAny, serde::de::IgnoredAny>()?
+                .is_some()
+            {}
+
+            Ok(())
+        }
+    }
+
+    deserializer.deserialize_option(EmptyParameters)
 }
 
 #[cfg(feature = "io-buffer-1mb")]
@@ -319,7 +834,7 @@ const BUFFER_SIZE: usize = 4 * 1024;
 #[cfg(feature = "std")]
 const MAX_BUFFER_SIZE: usize = 100 * 1024 * 1024; // Don't allow buffers over 100MB.
 
-fn from_slice<'a, T, ReplyError>(buffer: &'a [u8]) -> crate::Result<T, ReplyError>
+pub(crate) fn from_slice<'a, T, ReplyError>(buffer: &'a [u8]) -> crate::Result<T, ReplyError>
 where
     T: Deserialize<'a>,
 {
@@ -353,3 +868,281 @@ where
         serde_json_core::to_slice(value, buf).map_err(Into::into)
     }
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::num::NonZeroUsize;
+
+    use futures_executor::block_on;
+    use futures_util::future::join;
+
+    use super::*;
+    use crate::varlink_service;
+
+    #[test]
+    fn call_method() {
+        let channel = Channel::new();
+        let (client, server) = channel.split();
+        let (mut client, mut server) = (Connection::new(client), Connection::new(server));
+
+        let call = async {
+            let reply = client
+                .call_method::<_, Sum, MathError>(Method::Add { a: 1, b: 2 })
+                .await
+                .unwrap();
+            assert_eq!(reply.parameters().unwrap().sum, 3);
+            assert_eq!(reply.continues(), None);
+
+            let reply = client
+                .call_method::<_, Sum, MathError>(Method::Add { a: i64::MAX, b: 1 })
+                .await;
+            assert!(matches!(
+                reply,
+                Err(crate::Error::Reply(MathError::Overflow))
+            ));
+
+            // Standard errors are returned as such if `ReplyError` doesn't cover them.
+            let reply = client
+                .call_method::<_, Sum, MathError>(Method::Add { a: 0, b: 0 })
+                .await;
+            assert!(matches!(
+                reply,
+                Err(crate::Error::VarlinkService(
+                    varlink_service::Error::PermissionDenied
+                ))
+            ));
+        };
+        let serve = async {
+            for _ in 0..3 {
+                let call = server.receive_call::<Method, MathError>().await.unwrap();
+                assert_eq!(call.oneway(), None);
+                assert_eq!(call.more(), None);
+                let Method::Add { a, b } = call.into_method();
+                match a.checked_add(b) {
+                    Some(0) => server
+                        .send_error(varlink_service::Error::PermissionDenied)
+                        .await
+                        .unwrap(),
+                    Some(sum) => server
+                        .send_reply::<_, MathError>(Some(Sum { sum }), None)
+                        .await
+                        .unwrap(),
+                    None => server.send_error(MathError::Overflow).await.unwrap(),
+                }
+            }
+        };
+        block_on(join(call, serve));
+    }
+
+    #[test]
+    fn fragmented_and_coalesced_messages() {
+        let channel = Channel::new();
+        let (client, server) = channel.split();
+        let (mut client, mut server) = (Connection::new(client), Connection::new(server));
+
+        block_on(async {
+            // Both calls are read at once and received one after the other.
+            for a in [1, 2] {
+                client
+                    .send_call::<_, MathError>(Method::Add { a, b: 0 }, None, None, None)
+                    .await
+                    .unwrap();
+            }
+            for a in [1, 2] {
+                let call = server.receive_call::<Method, MathError>().await.unwrap();
+                assert!(matches!(call.into_method(), Method::Add { a: n, .. } if n == a));
+            }
+
+            // A message split across many reads.
+            channel.set_max_read_size(NonZeroUsize::new(3));
+            server
+                .send_reply::<_, MathError>(Some(Sum { sum: 42 }), None)
+                .await
+                .unwrap();
+            let reply = client.receive_reply::<Sum, MathError>().await.unwrap();
+            assert_eq!(reply.parameters().unwrap().sum, 42);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fds_message_offset() {
+        let buffer = b"{\"method\":\"A\"}\0{\"method\":\"B\"}\0{\"method\":\"C\"}\0";
+        let (second, third) = (15, 30);
+
+        // The read is a single message.
+        for fds_with_last_byte in [true, false] {
+            let offset = super::fds_message_offset(buffer, second, third, fds_with_last_byte);
+            assert_eq!(offset, second);
+        }
+
+        // The read contains the end of the first message and the start of the second one.
+        assert_eq!(super::fds_message_offset(buffer, 5, 20, true), second);
+        assert_eq!(super::fds_message_offset(buffer, 5, 20, false), 0);
+
+        // The read contains several full messages.
+        assert_eq!(
+            super::fds_message_offset(buffer, 0, buffer.len(), true),
+            third
+        );
+        assert_eq!(super::fds_message_offset(buffer, 0, buffer.len(), false), 0);
+
+        // The read starts right after the end of a message.
+        assert_eq!(super::fds_message_offset(buffer, second, 20, false), second);
+        assert_eq!(
+            super::fds_message_offset(buffer, third, buffer.len(), true),
+            third
+        );
+    }
+
+    // Emulates how Linux splits the data into reads: the file descriptors come with the last
+    // chunk of data of a read, which may then also contain earlier data.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn fds_per_message() {
+        use std::{
+            collections::VecDeque,
+            fs::File,
+            os::fd::{AsRawFd, RawFd},
+            vec::Vec,
+        };
+
+        let calls: Vec<_> = (1..=4)
+            .map(|a| {
+                std::format!(
+                    r#"{{"method":"org.example.math.Add","parameters":{{"a":{a},"b":0}}}}"#
+                )
+            })
+            .collect();
+        let open = || OwnedFd::from(File::open("/dev/null").unwrap());
+        let (fd2, fd3) = (open(), open());
+        let (raw2, raw3) = (fd2.as_raw_fd(), fd3.as_raw_fd());
+        // The second and third calls both come with a file descriptor and the data of both, along
+        // with the first call, is read before any call is received. The second call is split
+        // across the two reads.
+        let (second_start, second_end) = calls[1].split_at(10);
+        let reads = VecDeque::from([
+            (std::format!("{}\0{second_start}", calls[0]), std::vec![fd2]),
+            (std::format!("{second_end}\0{}\0", calls[2]), std::vec![fd3]),
+            (std::format!("{}\0", calls[3]), Vec::new()),
+        ]);
+        let mut connection = Connection::new(Reads(reads));
+
+        block_on(async {
+            // The first read ends in the middle of the second call so both reads are done before
+            // the first call is received.
+            connection.wait_for_message::<MathError>().await.unwrap();
+            assert_eq!(connection.socket().0.len(), 1);
+
+            let mut received: Vec<Vec<RawFd>> = Vec::new();
+            for a in 1..=3 {
+                let call = connection
+                    .receive_call::<Method, MathError>()
+                    .await
+                    .unwrap();
+                assert!(matches!(call.method(), Method::Add { a: n, .. } if *n == a));
+                received.push(call.fds().iter().map(AsRawFd::as_raw_fd).collect());
+            }
+            assert_eq!(received, [std::vec![], std::vec![raw2], std::vec![raw3]]);
+
+            let (call, fds) = connection
+                .receive_call_with_fds::<Method, MathError>()
+                .await
+                .unwrap();
+            assert!(matches!(call.method(), Method::Add { a: 4, .. }));
+            assert!(fds.is_empty());
+        });
+    }
+
+    // A socket returning the given data and file descriptors for each read.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[derive(Debug)]
+    struct Reads(std::collections::VecDeque<(std::string::String, std::vec::Vec<OwnedFd>)>);
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    impl Socket for Reads {
+        async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> crate::Result<usize, ReplyError> {
+            self.read_with_fds(buf, &mut std::vec::Vec::new()).await
+        }
+
+        async fn write<ReplyError>(&mut self, _: &[u8]) -> crate::Result<(), ReplyError> {
+            Ok(())
+        }
+
+        async fn read_with_fds<ReplyError>(
+            &mut self,
+            buf: &mut [u8],
+            fds: &mut std::vec::Vec<OwnedFd>,
+        ) -> crate::Result<usize, ReplyError> {
+            let Some((data, read_fds)) = self.0.pop_front() else {
+                return Ok(0);
+            };
+            buf[..data.len()].copy_from_slice(data.as_bytes());
+            fds.extend(read_fds);
+
+            Ok(data.len())
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn peer_credentials_failure_cached() {
+        use core::cell::Cell;
+
+        let queries = Cell::new(0);
+        let mut connection = Connection::new(NoCredentials(&queries));
+        for _ in 0..2 {
+            match connection.peer_credentials::<MathError>() {
+                Err(crate::Error::Io(e)) => {
+                    assert_eq!(e.kind(), std::io::ErrorKind::Unsupported)
+                }
+                r => panic!("unexpected result: {r:?}"),
+            }
+        }
+        assert_eq!(queries.get(), 1);
+    }
+
+    // A socket that doesn't support peer credentials, counting the attempts to fetch them.
+    #[cfg(unix)]
+    #[derive(Debug)]
+    struct NoCredentials<'q>(&'q core::cell::Cell<usize>);
+
+    #[cfg(unix)]
+    impl Socket for NoCredentials<'_> {
+        async fn read<ReplyError>(&mut self, _: &mut [u8]) -> crate::Result<usize, ReplyError> {
+            Ok(0)
+        }
+
+        async fn write<ReplyError>(&mut self, _: &[u8]) -> crate::Result<(), ReplyError> {
+            Ok(())
+        }
+
+        fn peer_credentials(&self) -> std::io::Result<Credentials> {
+            self.0.set(self.0.get() + 1);
+
+            Err(std::io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "method", content = "parameters")]
+    enum Method {
+        #[serde(rename = "org.example.math.Add")]
+        Add { a: i64, b: i64 },
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Sum {
+        sum: i64,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "error", content = "parameters")]
+    enum MathError {
+        #[serde(
+            rename = "org.example.math.Overflow",
+            deserialize_with = "crate::connection::empty_parameters"
+        )]
+        Overflow,
+    }
+}
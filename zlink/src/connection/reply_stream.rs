@@ -0,0 +1,171 @@
+use serde::Deserialize;
+
+use super::{Connection, Reply, Socket};
+
+/// A stream of replies to a method call that was sent with the `more` flag set.
+///
+/// This is very similar to a [`futures_util::Stream`] but since the replies are deserialized from
+/// the internal buffer of the connection and can borrow from it, a reply must be dropped before
+/// the next one can be received. Hence the stream is consumed by calling [`ReplyStream::next`] in
+/// a loop.
+///
+/// The stream ends after a reply without the `continues` flag set or an error is received.
+#[derive(Debug)]
+pub struct ReplyStream<'c, S: Socket> {
+    connection: &'c mut Connection<S>,
+    done: bool,
+}
+
+impl<'c, S: Socket> ReplyStream<'c, S> {
+    pub(super) fn new(connection: &'c mut Connection<S>) -> Self {
+        Self {
+            connection,
+            done: false,
+        }
+    }
+
+    /// Receive the next reply.
+    ///
+    /// Returns `None` if all replies have already been received. See [`Connection::receive_reply`]
+    /// for details on the generic parameters.
+    pub async fn next<'r, Params, ReplyError>(
+        &'r mut self,
+    ) -> Option<crate::Result<Reply<Params>, ReplyError>>
+    where
+        Params: Deserialize<'r>,
+        ReplyError: Deserialize<'r>,
+    {
+        if self.done {
+            return None;
+        }
+
+        let reply = self.connection.receive_reply().await;
+        match &reply {
+            Ok(reply) if reply.continues() == Some(true) => (),
+            _ => self.done = true,
+        }
+
+        Some(reply)
+    }
+
+    /// If all replies have been received.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::vec::Vec;
+
+    use futures_executor::block_on;
+    use futures_util::future::join;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::connection::Channel;
+
+    #[test]
+    fn replies() {
+        let channel = Channel::new();
+        let (client, server) = channel.split();
+        let (mut client, mut server) = (Connection::new(client), Connection::new(server));
+
+        let receive = async {
+            let mut stream = client
+                .call_method_more::<_, CountError>(Method::Count)
+                .await
+                .unwrap();
+            let mut replies = Vec::new();
+            while let Some(reply) = stream.next::<Count, CountError>().await {
+                let reply = reply.unwrap();
+                replies.push((reply.parameters().map(|c| c.count), reply.continues()));
+            }
+            assert!(stream.is_done());
+            // Nothing more is read from the connection once the stream is done.
+            assert!(stream.next::<Count, CountError>().await.is_none());
+
+            replies
+        };
+        let send = async {
+            let call = server.receive_call::<Method, CountError>().await.unwrap();
+            assert_eq!(call.more(), Some(true));
+            for count in 1..=2 {
+                server
+                    .send_reply::<_, CountError>(Some(Count { count }), Some(true))
+                    .await
+                    .unwrap();
+            }
+            // The final reply without parameters, as sent by services that don't know when the
+            // stream ends until it does.
+            server
+                .send_reply::<Count, CountError>(None, None)
+                .await
+                .unwrap();
+        };
+        let (replies, ()) = block_on(join(receive, send));
+        assert_eq!(
+            replies,
+            [(Some(1), Some(true)), (Some(2), Some(true)), (None, None)]
+        );
+    }
+
+    #[test]
+    fn error() {
+        let channel = Channel::new();
+        let (client, server) = channel.split();
+        let (mut client, mut server) = (Connection::new(client), Connection::new(server));
+
+        let receive = async {
+            let mut stream = client
+                .call_method_more::<_, CountError>(Method::Count)
+                .await
+                .unwrap();
+            let reply = stream.next::<Count, CountError>().await.unwrap().unwrap();
+            assert_eq!(reply.parameters().unwrap().count, 1);
+            assert!(!stream.is_done());
+            let reply = stream.next::<Count, CountError>().await.unwrap();
+            assert!(matches!(
+                reply,
+                Err(crate::Error::Reply(CountError::Overflow))
+            ));
+            // An error ends the stream.
+            assert!(stream.is_done());
+            assert!(stream.next::<Count, CountError>().await.is_none());
+        };
+        let send = async {
+            server.receive_call::<Method, CountError>().await.unwrap();
+            server
+                .send_reply::<_, CountError>(Some(Count { count: 1 }), Some(true))
+                .await
+                .unwrap();
+            server.send_error(CountError::Overflow).await.unwrap();
+        };
+        block_on(join(receive, send));
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "method", content = "parameters")]
+    enum Method {
+        #[serde(
+            rename = "org.example.counter.Count",
+            deserialize_with = "crate::connection::empty_parameters"
+        )]
+        Count,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Count {
+        count: i64,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "error", content = "parameters")]
+    enum CountError {
+        #[serde(
+            rename = "org.example.counter.Overflow",
+            deserialize_with = "crate::connection::empty_parameters"
+        )]
+        Overflow,
+    }
+}
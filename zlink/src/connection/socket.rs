@@ -17,4 +17,56 @@ pub trait Socket {
         &mut self,
         buf: &[u8],
     ) -> impl Future<Output = crate::Result<(), ReplyError>>;
+
+    /// Read from the socket, along with any file descriptors passed with the data.
+    ///
+    /// The received file descriptors are appended to `fds`. The default implementation doesn't
+    /// receive any file descriptors.
+    ///
+    /// The data the file descriptors are passed with must not be merged with later data in a
+    /// single read, as Unix domain sockets do, so that the connection can tell which message they
+    /// belong to.
+    #[cfg(all(feature = "std", unix))]
+    fn read_with_fds<ReplyError>(
+        &mut self,
+        buf: &mut [u8],
+        fds: &mut std::vec::Vec<std::os::fd::OwnedFd>,
+    ) -> impl Future<Output = crate::Result<usize, ReplyError>> {
+        let _ = fds;
+
+        self.read(buf)
+    }
+
+    /// Write to the socket, passing the file descriptors `fds` along with the data.
+    ///
+    /// The default implementation fails if `fds` is not empty.
+    #[cfg(all(feature = "std", unix))]
+    fn write_with_fds<ReplyError>(
+        &mut self,
+        buf: &[u8],
+        fds: &[std::os::fd::BorrowedFd<'_>],
+    ) -> impl Future<Output = crate::Result<(), ReplyError>> {
+        async move {
+            if !fds.is_empty() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "socket doesn't support passing file descriptors",
+                )
+                .into());
+            }
+
+            self.write(buf).await
+        }
+    }
+
+    /// The credentials of the peer process.
+    ///
+    /// The default implementation fails with [`std::io::ErrorKind::Unsupported`].
+    #[cfg(all(feature = "std", unix))]
+    fn peer_credentials(&self) -> std::io::Result<super::Credentials> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "socket doesn't support peer credentials",
+        ))
+    }
 }
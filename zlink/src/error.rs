@@ -3,6 +3,8 @@
 pub enum Error<ReplyError = &'static str> {
     /// An error from the service.
     Reply(ReplyError),
+    /// A standard `org.varlink.service` error from the service.
+    VarlinkService(crate::varlink_service::Error),
     /// An error occurred while reading from the socket.
     SocketRead,
     /// An error occurred while writing to the socket.
@@ -40,6 +42,7 @@ where
             Error::JsonDeserialize(e) => Some(e),
             #[cfg(feature = "std")]
             Error::Io(e) => Some(e),
+            Error::VarlinkService(e) => Some(e),
             _ => None,
         }
     }
@@ -80,6 +83,7 @@ where
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Error::Reply(e) => write!(f, "server error: {e}"),
+            Error::VarlinkService(e) => write!(f, "server error: {e}"),
             Error::SocketRead => write!(f, "An error occurred while reading from the socket"),
             Error::SocketWrite => write!(f, "An error occurred while writing to the socket"),
             Error::BufferOverflow => write!(f, "Buffer overflow"),
@@ -0,0 +1,130 @@
+//! Contains the Varlink [interface definition language (IDL)][idl] API.
+//!
+//! The main entry point is [`Interface::parse`], which parses the IDL of a Varlink interface into
+//! a typed syntax tree. All the names and comments in the tree borrow from the parsed text.
+//!
+//! [idl]: https://varlink.org/Interface-Definition
+
+mod parser;
+pub use parser::ParseError;
+
+use alloc::{boxed::Box, vec::Vec};
+
+/// A Varlink interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface<'a> {
+    /// The fully-qualified name of the interface, e.g `org.varlink.service`.
+    pub name: &'a str,
+    /// The comments preceding the interface declaration.
+    pub comments: Vec<&'a str>,
+    /// The custom types declared in the interface.
+    pub types: Vec<CustomType<'a>>,
+    /// The methods declared in the interface.
+    pub methods: Vec<Method<'a>>,
+    /// The errors declared in the interface.
+    pub errors: Vec<ErrorType<'a>>,
+}
+
+impl<'a> Interface<'a> {
+    /// Parse the IDL of an interface.
+    pub fn parse(idl: &'a str) -> Result<Self, ParseError> {
+        parser::Parser::new(idl).parse_interface()
+    }
+
+    /// Find a custom type by its name.
+    pub fn custom_type(&self, name: &str) -> Option<&CustomType<'a>> {
+        self.types.iter().find(|t| t.name == name)
+    }
+
+    /// Find a method by its name, without the interface name.
+    pub fn method(&self, name: &str) -> Option<&Method<'a>> {
+        self.methods.iter().find(|m| m.name == name)
+    }
+
+    /// Find an error by its name, without the interface name.
+    pub fn error(&self, name: &str) -> Option<&ErrorType<'a>> {
+        self.errors.iter().find(|e| e.name == name)
+    }
+}
+
+/// A custom type declaration (`type Name (...)`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomType<'a> {
+    /// The name of the type.
+    pub name: &'a str,
+    /// The comments preceding the type declaration.
+    pub comments: Vec<&'a str>,
+    /// The definition of the type. This is always either a [`Type::Struct`] or a [`Type::Enum`].
+    pub ty: Type<'a>,
+}
+
+/// A method declaration (`method Name (...) -> (...)`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Method<'a> {
+    /// The name of the method, without the interface name.
+    pub name: &'a str,
+    /// The comments preceding the method declaration.
+    pub comments: Vec<&'a str>,
+    /// The input parameters.
+    pub inputs: Vec<Field<'a>>,
+    /// The output parameters.
+    pub outputs: Vec<Field<'a>>,
+}
+
+/// An error declaration (`error Name (...)`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorType<'a> {
+    /// The name of the error, without the interface name.
+    pub name: &'a str,
+    /// The comments preceding the error declaration.
+    pub comments: Vec<&'a str>,
+    /// The parameters of the error.
+    pub fields: Vec<Field<'a>>,
+}
+
+/// A field of a struct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field<'a> {
+    /// The name of the field.
+    pub name: &'a str,
+    /// The comments preceding the field.
+    pub comments: Vec<&'a str>,
+    /// The type of the field.
+    pub ty: Type<'a>,
+}
+
+/// A variant of an enum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumVariant<'a> {
+    /// The name of the variant.
+    pub name: &'a str,
+    /// The comments preceding the variant.
+    pub comments: Vec<&'a str>,
+}
+
+/// A Varlink type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type<'a> {
+    /// `bool`.
+    Bool,
+    /// `int`.
+    Int,
+    /// `float`.
+    Float,
+    /// `string`.
+    String,
+    /// `object`, i-e any JSON object.
+    Object,
+    /// A reference to a custom type declared in the interface.
+    Custom(&'a str),
+    /// A nullable type (`?type`).
+    Optional(Box<Type<'a>>),
+    /// An array (`[]type`).
+    Array(Box<Type<'a>>),
+    /// A dictionary with string keys (`[string]type`).
+    Map(Box<Type<'a>>),
+    /// An enum (`(a, b, c)`).
+    Enum(Vec<EnumVariant<'a>>),
+    /// A struct (`(a: type, b: type)`).
+    Struct(Vec<Field<'a>>),
+}
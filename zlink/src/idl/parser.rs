@@ -0,0 +1,550 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use super::{CustomType, EnumVariant, ErrorType, Field, Interface, Method, Type};
+
+/// An error from parsing an interface definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    column: usize,
+    message: &'static str,
+}
+
+impl ParseError {
+    /// The line at which the error occurred, starting from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column at which the error occurred, starting from 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// The description of the error.
+    pub fn message(&self) -> &'static str {
+        self.message
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+type Result<T> = core::result::Result<T, ParseError>;
+
+// The maximum number of types a type can be nested in.
+const MAX_TYPE_DEPTH: usize = 64;
+
+// A recursive descent parser for the Varlink IDL.
+pub(super) struct Parser<'a> {
+    input: &'a str,
+    // Byte offset of the next character.
+    pos: usize,
+    line: usize,
+    column: usize,
+    // How many types the type being parsed is nested in.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(super) fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            line: 1,
+            column: 1,
+            depth: 0,
+        }
+    }
+
+    pub(super) fn parse_interface(mut self) -> Result<Interface<'a>> {
+        let comments = self.skip_whitespace();
+        if !self.keyword("interface") {
+            return Err(self.error("expected `interface`"));
+        }
+        self.skip_whitespace();
+        let name = self.interface_name()?;
+
+        let mut interface = Interface {
+            name,
+            comments,
+            types: Vec::new(),
+            methods: Vec::new(),
+            errors: Vec::new(),
+        };
+        loop {
+            let comments = self.skip_whitespace();
+            if self.peek().is_none() {
+                break;
+            }
+
+            if self.keyword("type") {
+                self.skip_whitespace();
+                let name = self.name()?;
+                self.skip_whitespace();
+                let ty = self.struct_or_enum()?;
+                interface.types.push(CustomType { name, comments, ty });
+            } else if self.keyword("method") {
+                self.skip_whitespace();
+                let name = self.name()?;
+                self.skip_whitespace();
+                let inputs = self.struct_fields()?;
+                self.skip_whitespace();
+                self.expect("->", "expected `->`")?;
+                self.skip_whitespace();
+                let outputs = self.struct_fields()?;
+                interface.methods.push(Method {
+                    name,
+                    comments,
+                    inputs,
+                    outputs,
+                });
+            } else if self.keyword("error") {
+                self.skip_whitespace();
+                let name = self.name()?;
+                self.skip_whitespace();
+                let fields = self.struct_fields()?;
+                interface.errors.push(ErrorType {
+                    name,
+                    comments,
+                    fields,
+                });
+            } else {
+                return Err(self.error("expected `type`, `method` or `error`"));
+            }
+        }
+        if interface.methods.is_empty() && interface.types.is_empty() && interface.errors.is_empty()
+        {
+            return Err(self.error("expected at least one member in the interface"));
+        }
+
+        Ok(interface)
+    }
+
+    // Parses a type, including the optional `?` prefix.
+    //
+    // The input may come from an untrusted peer so the nesting of types, and hence the recursion,
+    // is limited.
+    fn ty(&mut self) -> Result<Type<'a>> {
+        if self.depth == MAX_TYPE_DEPTH {
+            return Err(self.error("types are nested too deeply"));
+        }
+
+        self.depth += 1;
+        let ty = self.nested_ty();
+        self.depth -= 1;
+
+        ty
+    }
+
+    fn nested_ty(&mut self) -> Result<Type<'a>> {
+        if self.eat("?") {
+            if self.peek() == Some('?') {
+                return Err(self.error("nested nullable types are not allowed"));
+            }
+
+            return self.ty().map(Box::new).map(Type::Optional);
+        }
+
+        if self.eat("[") {
+            if self.eat("]") {
+                return self.ty().map(Box::new).map(Type::Array);
+            }
+            if self.keyword("string") && self.eat("]") {
+                return self.ty().map(Box::new).map(Type::Map);
+            }
+
+            return Err(self.error("expected `]` or `string]`"));
+        }
+
+        if self.peek() == Some('(') {
+            return self.struct_or_enum();
+        }
+
+        let (line, column) = (self.line, self.column);
+        let ty = match self.identifier() {
+            "bool" => Type::Bool,
+            "int" => Type::Int,
+            "float" => Type::Float,
+            "string" => Type::String,
+            "object" => Type::Object,
+            "" => return Err(self.error("expected a type")),
+            name if name.starts_with(|c: char| c.is_ascii_uppercase()) => Type::Custom(name),
+            _ => return Err(Self::error_at(line, column, "unknown type")),
+        };
+
+        Ok(ty)
+    }
+
+    // Parses an anonymous struct or enum.
+    fn struct_or_enum(&mut self) -> Result<Type<'a>> {
+        self.expect("(", "expected `(`")?;
+
+        let mut fields = Vec::new();
+        let mut variants = Vec::new();
+        loop {
+            let comments = self.skip_whitespace();
+            if fields.is_empty() && variants.is_empty() && self.eat(")") {
+                break;
+            }
+
+            let (line, column) = (self.line, self.column);
+            let name = self.field_name()?;
+            self.skip_whitespace();
+            if self.eat(":") {
+                if !variants.is_empty() {
+                    return Err(Self::error_at(line, column, "expected an enum variant"));
+                }
+                self.skip_whitespace();
+                let ty = self.ty()?;
+                fields.push(Field { name, comments, ty });
+            } else {
+                if !fields.is_empty() {
+                    return Err(self.error("expected `:`"));
+                }
+                variants.push(EnumVariant { name, comments });
+            }
+
+            self.skip_whitespace();
+            if !self.eat(",") {
+                self.expect(")", "expected `,` or `)`")?;
+                break;
+            }
+        }
+
+        if variants.is_empty() {
+            Ok(Type::Struct(fields))
+        } else {
+            Ok(Type::Enum(variants))
+        }
+    }
+
+    // Parses a struct and returns its fields.
+    fn struct_fields(&mut self) -> Result<Vec<Field<'a>>> {
+        let (line, column) = (self.line, self.column);
+        match self.struct_or_enum()? {
+            Type::Struct(fields) => Ok(fields),
+            _ => Err(Self::error_at(line, column, "expected a struct")),
+        }
+    }
+
+    // Parses an interface name, e.g `org.example.ftl`.
+    fn interface_name(&mut self) -> Result<&'a str> {
+        let (line, column) = (self.line, self.column);
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+        let valid = name.contains('.')
+            && name.split('.').all(|part| {
+                part.starts_with(|c: char| c.is_ascii_alphanumeric()) && !part.ends_with('-')
+            });
+        if !valid {
+            return Err(Self::error_at(line, column, "invalid interface name"));
+        }
+
+        Ok(name)
+    }
+
+    // Parses the name of a type, method or error.
+    fn name(&mut self) -> Result<&'a str> {
+        let (line, column) = (self.line, self.column);
+        let name = self.identifier();
+        if !name.starts_with(|c: char| c.is_ascii_uppercase()) || name.contains('_') {
+            return Err(Self::error_at(
+                line,
+                column,
+                "expected a name starting with an uppercase letter",
+            ));
+        }
+
+        Ok(name)
+    }
+
+    // Parses the name of a field or an enum variant.
+    fn field_name(&mut self) -> Result<&'a str> {
+        let (line, column) = (self.line, self.column);
+        let name = self.identifier();
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Err(Self::error_at(line, column, "expected a field name"));
+        }
+
+        Ok(name)
+    }
+
+    fn identifier(&mut self) -> &'a str {
+        self.take_while(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    // Consumes `keyword` if it's next in the input, as a whole word.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let rest = &self.input[self.pos..];
+        let whole_word = rest.starts_with(keyword)
+            && !rest[keyword.len()..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_');
+        if whole_word {
+            self.advance(keyword.len());
+        }
+
+        whole_word
+    }
+
+    // Consumes `token` if it's next in the input.
+    fn eat(&mut self, token: &str) -> bool {
+        if self.input[self.pos..].starts_with(token) {
+            self.advance(token.len());
+
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str, message: &'static str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    // Skips whitespace and comments, returning the comments.
+    fn skip_whitespace(&mut self) -> Vec<&'a str> {
+        let mut comments = Vec::new();
+        loop {
+            self.take_while(char::is_whitespace);
+            if !self.eat("#") {
+                break;
+            }
+
+            let comment = self.take_while(|c| c != '\n');
+            comments.push(comment.strip_prefix(' ').unwrap_or(comment).trim_end());
+        }
+
+        comments
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !f(c) {
+                break;
+            }
+            self.advance(c.len_utf8());
+        }
+
+        &self.input[start..self.pos]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    // Advances by `len` bytes, which must be on a character boundary.
+    fn advance(&mut self, len: usize) {
+        for c in self.input[self.pos..self.pos + len].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.pos += len;
+    }
+
+    fn error(&self, message: &'static str) -> ParseError {
+        Self::error_at(self.line, self.column, message)
+    }
+
+    fn error_at(line: usize, column: usize, message: &'static str) -> ParseError {
+        ParseError {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let interface = Interface::parse(
+            r#"
+# The Varlink Service Interface.
+interface org.varlink.service
+
+type Interface (
+  name: string,
+  # The description of the interface.
+  description: ?string
+)
+
+type State (idle, busy)
+
+# Get a list of all the interfaces a service provides.
+method GetInfo() -> (
+  vendor: string,
+  interfaces: []string,
+  properties: [string]?(value: int, flags: []State)
+)
+
+error InterfaceNotFound (interface: string)
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(interface.name, "org.varlink.service");
+        assert_eq!(interface.comments, ["The Varlink Service Interface."]);
+        assert_eq!(interface.types.len(), 2);
+        let description = match &interface.custom_type("Interface").unwrap().ty {
+            Type::Struct(fields) => &fields[1],
+            ty => panic!("unexpected type: {ty:?}"),
+        };
+        assert_eq!(description.name, "description");
+        assert_eq!(description.comments, ["The description of the interface."]);
+        assert_eq!(description.ty, Type::Optional(Box::new(Type::String)));
+        let Type::Enum(variants) = &interface.custom_type("State").unwrap().ty else {
+            panic!("`State` is not an enum");
+        };
+        let variants: Vec<_> = variants.iter().map(|v| v.name).collect();
+        assert_eq!(variants, ["idle", "busy"]);
+
+        let method = interface.method("GetInfo").unwrap();
+        assert_eq!(
+            method.comments,
+            ["Get a list of all the interfaces a service provides."]
+        );
+        assert!(method.inputs.is_empty());
+        assert_eq!(method.outputs[1].ty, Type::Array(Box::new(Type::String)));
+        let Type::Map(value) = &method.outputs[2].ty else {
+            panic!("`properties` is not a map");
+        };
+        let Type::Optional(value) = &**value else {
+            panic!("`properties` values are not nullable");
+        };
+        let Type::Struct(fields) = &**value else {
+            panic!("`properties` values are not structs");
+        };
+        assert_eq!(fields[1].ty, Type::Array(Box::new(Type::Custom("State"))));
+
+        let error = interface.error("InterfaceNotFound").unwrap();
+        assert_eq!(error.fields[0].name, "interface");
+        assert_eq!(error.fields[0].ty, Type::String);
+    }
+
+    #[test]
+    fn nesting_limit() {
+        use alloc::{format, string::String};
+
+        let nested = |open: &str, close: &str, depth: usize| {
+            let mut ty = String::new();
+            for _ in 0..depth {
+                ty.push_str(open);
+            }
+            ty.push_str("int");
+            for _ in 0..depth {
+                ty.push_str(close);
+            }
+
+            format!("interface org.foo\nmethod Foo(a: {ty}) -> ()")
+        };
+
+        Parser::new(&nested("[]", "", MAX_TYPE_DEPTH - 1))
+            .parse_interface()
+            .unwrap();
+        for (open, close) in [("[]", ""), ("(a: ", ")"), ("?(a: ", ")")] {
+            let error = Parser::new(&nested(open, close, 100_000))
+                .parse_interface()
+                .unwrap_err();
+            assert_eq!(error.message(), "types are nested too deeply");
+            assert_eq!(error.line(), 2);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("method Foo() -> ()", 1, 1, "expected `interface`"),
+            (
+                "interface foo\nmethod Foo() -> ()",
+                1,
+                11,
+                "invalid interface name",
+            ),
+            (
+                "interface org.foo\n",
+                2,
+                1,
+                "expected at least one member in the interface",
+            ),
+            (
+                "interface org.foo\nmethod foo() -> ()",
+                2,
+                8,
+                "expected a name starting with an uppercase letter",
+            ),
+            ("interface org.foo\nmethod Foo() ()", 2, 14, "expected `->`"),
+            (
+                "interface org.foo\nmethod Foo(a: int b: int) -> ()",
+                2,
+                19,
+                "expected `,` or `)`",
+            ),
+            (
+                "interface org.foo\nmethod Foo(a: ??int) -> ()",
+                2,
+                16,
+                "nested nullable types are not allowed",
+            ),
+            (
+                "interface org.foo\nmethod Foo(a: [int]bool) -> ()",
+                2,
+                16,
+                "expected `]` or `string]`",
+            ),
+            (
+                "interface org.foo\n\nmethod Foo(a: integer) -> ()",
+                3,
+                15,
+                "unknown type",
+            ),
+            (
+                "interface org.foo\nmethod Foo(a, b) -> ()",
+                2,
+                11,
+                "expected a struct",
+            ),
+            (
+                "interface org.foo\ntype Foo (a, b: int)",
+                2,
+                14,
+                "expected an enum variant",
+            ),
+            (
+                "interface org.foo\ntype Foo (a: int, b)",
+                2,
+                20,
+                "expected `:`",
+            ),
+            (
+                "interface org.foo\nerror Foo (a: int)\nfoo",
+                3,
+                1,
+                "expected `type`, `method` or `error`",
+            ),
+        ];
+        for (idl, line, column, message) in cases {
+            let error = Interface::parse(idl).unwrap_err();
+            assert_eq!(
+                (error.line(), error.column(), error.message()),
+                (line, column, message),
+                "{idl}"
+            );
+        }
+    }
+}
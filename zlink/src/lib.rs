@@ -11,7 +11,54 @@
 #[cfg(all(not(feature = "std"), not(feature = "embedded")))]
 compile_error!("Either 'std' or 'embedded' feature must be enabled.");
 
+#[cfg(feature = "idl")]
+extern crate alloc;
+
+pub mod address;
+pub use address::Address;
 pub mod connection;
 pub use connection::Connection;
 mod error;
 pub use error::{Error, Result};
+#[cfg(feature = "idl")]
+pub mod idl;
+#[cfg(feature = "mux")]
+pub mod mux;
+pub mod server;
+pub use server::Server;
+#[cfg(all(feature = "unix-socket", unix))]
+pub mod unix;
+pub mod varlink_service;
+pub use zlink_macros::{proxy, service};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use futures_util;
+    pub use serde;
+
+    #[cfg(feature = "std")]
+    type DeError = serde_json::Error;
+    #[cfg(not(feature = "std"))]
+    type DeError = serde_json_core::de::Error;
+
+    // Used by the `proxy` macro to get the parameters out of a reply that must have them.
+    pub fn reply_parameters<Params, ReplyError>(
+        reply: crate::connection::Reply<Params>,
+    ) -> crate::Result<Params, ReplyError> {
+        reply
+            .into_parameters()
+            .ok_or_else(|| <DeError as serde::de::Error>::missing_field("parameters").into())
+    }
+
+    // Used by the `proxy` macro to get the parameters out of a reply to a method call with the
+    // `more` flag set. Services may end the stream of replies with a final one without parameters,
+    // which is not a reply of its own.
+    pub fn stream_reply_parameters<Params, ReplyError>(
+        reply: crate::Result<crate::connection::Reply<Params>, ReplyError>,
+    ) -> Option<crate::Result<Params, ReplyError>> {
+        match reply {
+            Ok(reply) if reply.parameters().is_none() && reply.continues() != Some(true) => None,
+            reply => Some(reply.and_then(reply_parameters)),
+        }
+    }
+}
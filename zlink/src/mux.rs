@@ -0,0 +1,663 @@
+//! Contains the multiplexing of several connections over a single pipe.
+//!
+//! This is what `zlink-micro` uses on the device side and `zlink-usb` on the host side, to have
+//! several connections over a single pair of USB endpoints.
+
+use core::{
+    cell::RefCell,
+    fmt,
+    future::poll_fn,
+    pin::pin,
+    task::{Poll, Waker},
+};
+
+use futures_util::future::{select, Either};
+use maitake_sync::Mutex;
+
+use crate::{connection::Socket, Connection, Result};
+
+/// Multiplexes several logical connections over a single pipe.
+///
+/// The data of each connection is sent over the pipe in frames, each starting with the ID of the
+/// connection (1 byte) and a 16-bit little-endian length field:
+///
+/// * A data frame has the length of its payload, which follows the header.
+/// * A frame with a length of zero closes the connection. It's sent once the [`Stream`] of the
+///   connection is dropped, after which the other side reads the end of the stream and its writes
+///   fail. An ID can only be used again once the connection has been closed on both sides.
+/// * A frame with the highest bit of the length set grants the other side credit to send that many
+///   more bytes (the length without the highest bit) for the connection.
+///
+/// Up to `N` connections, with IDs `0..N`, can be used at the same time. Both sides of the pipe
+/// need to use a multiplexer.
+///
+/// Each connection starts with enough credit to fill the buffer the other side keeps for it, and
+/// the credit is granted again as the data is read. Hence a connection that doesn't read its data,
+/// or that wasn't created yet on the receiving side, only holds back its own writer on the other
+/// side. Any data exceeding the credit, which only a misbehaving peer sends, is dropped.
+///
+/// The frames are written to `W` by the connections themselves, while reading from the pipe and
+/// sending the close and credit frames is done by [`Multiplexer::run`], which needs to be running
+/// for the connections to receive anything.
+///
+/// The multiplexer doesn't allocate and can be used from any executor, e.g that of embassy on the
+/// device side. A `critical-section` implementation is needed.
+pub struct Multiplexer<W, const N: usize = 4> {
+    writer: Mutex<W>,
+    inboxes: critical_section::Mutex<RefCell<[Inbox; N]>>,
+    // `run` waiting for close and credit frames to send.
+    control: critical_section::Mutex<RefCell<Option<Waker>>>,
+}
+
+impl<W, const N: usize> Multiplexer<W, N>
+where
+    W: Socket,
+{
+    /// Create a new multiplexer, writing the frames to `writer`.
+    pub fn new(writer: W) -> Self {
+        assert!(N <= MAX_CONNECTIONS, "too many connections");
+
+        Self {
+            writer: Mutex::new(writer),
+            inboxes: critical_section::Mutex::new(RefCell::new(core::array::from_fn(|_| {
+                Inbox::new()
+            }))),
+            control: critical_section::Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Create the connection with the given ID.
+    ///
+    /// Returns `None` if the ID is out of range, the connection already exists or it's still being
+    /// closed.
+    pub fn connection(&self, id: u8) -> Option<Connection<Stream<'_, W, N>>> {
+        self.stream(id).map(Connection::new)
+    }
+
+    /// Create the stream of the connection with the given ID.
+    ///
+    /// This is the same as [`Multiplexer::connection`], without the [`Connection`] wrapper.
+    pub fn stream(&self, id: u8) -> Option<Stream<'_, W, N>> {
+        self.with_inbox(id, |inbox| {
+            if inbox.claimed || inbox.closing {
+                return None;
+            }
+            // Any data already received for the connection is kept.
+            inbox.claimed = true;
+
+            Some(())
+        })??;
+
+        Some(Stream { mux: self, id })
+    }
+
+    /// Read the frames from `reader` and dispatch them to the connections.
+    ///
+    /// This also sends the close and credit frames. It only returns if reading or writing fails,
+    /// after which all the connections receive the end of the stream.
+    pub async fn run<R, ReplyError>(&self, mut reader: R) -> Result<(), ReplyError>
+    where
+        R: Socket,
+    {
+        let dispatch = pin!(self.dispatch(&mut reader));
+        let send_control = pin!(self.send_control());
+        let (Either::Left((res, _)) | Either::Right((res, _))) =
+            select(dispatch, send_control).await;
+
+        let wakers = critical_section::with(|cs| {
+            let mut inboxes = self.inboxes.borrow_ref_mut(cs);
+            let mut wakers: [(Option<Waker>, Option<Waker>); N] = [const { (None, None) }; N];
+            for (inbox, wakers) in inboxes.iter_mut().zip(wakers.iter_mut()) {
+                inbox.peer_closed = true;
+                *wakers = (inbox.reader.take(), inbox.writer.take());
+            }
+
+            wakers
+        });
+        for (reader, writer) in wakers {
+            wake(reader);
+            wake(writer);
+        }
+
+        res
+    }
+
+    async fn dispatch<R, ReplyError>(&self, reader: &mut R) -> Result<(), ReplyError>
+    where
+        R: Socket,
+    {
+        loop {
+            let mut header = [0; HEADER_SIZE];
+            read_exact(reader, &mut header).await?;
+            let id = header[0];
+            let len = u16::from_le_bytes([header[1], header[2]]);
+            if len == 0 {
+                self.close_received(id);
+
+                continue;
+            }
+            if len & CREDIT_FLAG != 0 {
+                self.credit_received(id, (len & !CREDIT_FLAG) as usize);
+
+                continue;
+            }
+
+            let mut remaining = len as usize;
+            let mut chunk = [0; 64];
+            while remaining > 0 {
+                let len = remaining.min(chunk.len());
+                read_exact(reader, &mut chunk[..len]).await?;
+                self.deliver(id, &chunk[..len]);
+                remaining -= len;
+            }
+        }
+    }
+
+    // Put `data` in the inbox of connection `id`.
+    //
+    // This never waits, so that no connection can hold back the others. Thanks to the credit, the
+    // data always fits unless the peer misbehaves.
+    fn deliver(&self, id: u8, data: &[u8]) {
+        let reader = self.with_inbox(id, |inbox| {
+            if inbox.closing {
+                // No one to deliver to anymore.
+                return None;
+            }
+            let n = (INBOX_SIZE - inbox.len).min(data.len());
+            inbox.data[inbox.len..inbox.len + n].copy_from_slice(&data[..n]);
+            inbox.len += n;
+
+            inbox.reader.take()
+        });
+        wake(reader.flatten());
+    }
+
+    // Handle the close frame of connection `id`.
+    fn close_received(&self, id: u8) {
+        let wakers = self.with_inbox(id, |inbox| {
+            inbox.peer_closed = true;
+            let wakers = (inbox.reader.take(), inbox.writer.take());
+            inbox.reset_if_closed();
+
+            wakers
+        });
+        if let Some((reader, writer)) = wakers {
+            wake(reader);
+            wake(writer);
+        }
+    }
+
+    // Handle the credit frame of connection `id`.
+    fn credit_received(&self, id: u8, credit: usize) {
+        let writer = self.with_inbox(id, |inbox| {
+            if inbox.closing {
+                return None;
+            }
+            inbox.credit = inbox.credit.saturating_add(credit);
+
+            inbox.writer.take()
+        });
+        wake(writer.flatten());
+    }
+
+    // Send the close frames of the connections dropped on our side and the credit for the data
+    // read.
+    async fn send_control<ReplyError>(&self) -> Result<(), ReplyError> {
+        loop {
+            let (id, len) = poll_fn(|cx| {
+                critical_section::with(|cs| {
+                    let mut inboxes = self.inboxes.borrow_ref_mut(cs);
+                    for (id, inbox) in inboxes.iter_mut().enumerate() {
+                        if inbox.close_pending {
+                            return Poll::Ready((id as u8, 0));
+                        }
+                        if inbox.unacked > 0 {
+                            let credit = inbox.unacked.min(MAX_CREDIT);
+                            inbox.unacked -= credit;
+
+                            return Poll::Ready((id as u8, credit as u16 | CREDIT_FLAG));
+                        }
+                    }
+                    // Registered while the inboxes are locked, so no update is missed.
+                    *self.control.borrow_ref_mut(cs) = Some(cx.waker().clone());
+
+                    Poll::Pending
+                })
+            })
+            .await;
+
+            let mut header = [0; HEADER_SIZE];
+            header[0] = id;
+            header[1..].copy_from_slice(&len.to_le_bytes());
+            self.writer.lock().await.write(&header).await?;
+            if len == 0 {
+                let _ = self.with_inbox(id, |inbox| {
+                    inbox.close_pending = false;
+                    inbox.reset_if_closed();
+                });
+            }
+        }
+    }
+
+    fn wake_control(&self) {
+        wake(critical_section::with(|cs| {
+            self.control.borrow_ref_mut(cs).take()
+        }));
+    }
+
+    fn with_inbox<T>(&self, id: u8, f: impl FnOnce(&mut Inbox) -> T) -> Option<T> {
+        critical_section::with(|cs| self.inboxes.borrow_ref_mut(cs).get_mut(id as usize).map(f))
+    }
+}
+
+impl<W, const N: usize> fmt::Debug for Multiplexer<W, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Multiplexer")
+            .field("connections", &N)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The [`Socket`] implementation of a logical connection of a [`Multiplexer`].
+///
+/// Use [`Multiplexer::connection`] to create a connection using it.
+#[derive(Debug)]
+pub struct Stream<'m, W, const N: usize> {
+    mux: &'m Multiplexer<W, N>,
+    id: u8,
+}
+
+impl<W, const N: usize> Stream<'_, W, N> {
+    /// The ID of the connection.
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+}
+
+impl<W, const N: usize> Socket for Stream<'_, W, N>
+where
+    W: Socket,
+{
+    async fn read<ReplyError>(&mut self, buf: &mut [u8]) -> Result<usize, ReplyError> {
+        let n = poll_fn(|cx| {
+            self.mux
+                .with_inbox(self.id, |inbox| {
+                    if inbox.len == 0 {
+                        if inbox.peer_closed {
+                            return Poll::Ready(0);
+                        }
+                        // Registered while the inbox is locked, so no data is missed.
+                        inbox.reader = Some(cx.waker().clone());
+
+                        return Poll::Pending;
+                    }
+                    let n = inbox.len.min(buf.len());
+                    buf[..n].copy_from_slice(&inbox.data[..n]);
+                    inbox.data.copy_within(n..inbox.len, 0);
+                    inbox.len -= n;
+                    inbox.unacked += n;
+
+                    Poll::Ready(n)
+                })
+                .unwrap_or(Poll::Ready(0))
+        })
+        .await;
+        if n > 0 {
+            // Grant the credit for the data read.
+            self.mux.wake_control();
+        }
+
+        Ok(n)
+    }
+
+    async fn write<ReplyError>(&mut self, mut buf: &[u8]) -> Result<(), ReplyError> {
+        // Empty payloads are for close frames so nothing is sent for an empty `buf`.
+        while !buf.is_empty() {
+            let len = poll_fn(|cx| {
+                self.mux
+                    .with_inbox(self.id, |inbox| {
+                        if inbox.peer_closed {
+                            return Poll::Ready(None);
+                        }
+                        if inbox.credit == 0 {
+                            // Registered while the inbox is locked, so no credit is missed.
+                            inbox.writer = Some(cx.waker().clone());
+
+                            return Poll::Pending;
+                        }
+                        let len = buf.len().min(inbox.credit).min(FRAME_PAYLOAD_SIZE);
+                        inbox.credit -= len;
+
+                        Poll::Ready(Some(len))
+                    })
+                    .unwrap_or(Poll::Ready(None))
+            })
+            .await
+            .ok_or(crate::Error::SocketWrite)?;
+
+            // Only hold the writer while writing the frame, not while waiting for credit, so the
+            // other connections can write in between.
+            let mut frame = [0; HEADER_SIZE + FRAME_PAYLOAD_SIZE];
+            frame[0] = self.id;
+            frame[1..HEADER_SIZE].copy_from_slice(&(len as u16).to_le_bytes());
+            frame[HEADER_SIZE..HEADER_SIZE + len].copy_from_slice(&buf[..len]);
+            self.mux
+                .writer
+                .lock()
+                .await
+                .write(&frame[..HEADER_SIZE + len])
+                .await?;
+            buf = &buf[len..];
+        }
+
+        Ok(())
+    }
+}
+
+impl<W, const N: usize> Drop for Stream<'_, W, N> {
+    fn drop(&mut self) {
+        let _ = self.mux.with_inbox(self.id, |inbox| {
+            inbox.claimed = false;
+            inbox.closing = true;
+            inbox.close_pending = true;
+            inbox.len = 0;
+            inbox.unacked = 0;
+            inbox.reader = None;
+            inbox.writer = None;
+        });
+        // `run` sends the close frame.
+        self.mux.wake_control();
+    }
+}
+
+// The buffered data and state of a connection.
+struct Inbox {
+    data: [u8; INBOX_SIZE],
+    len: usize,
+    // The number of bytes read that the other side wasn't granted credit for yet.
+    unacked: usize,
+    // The number of bytes we can still send.
+    credit: usize,
+    // The stream waiting for data.
+    reader: Option<Waker>,
+    // The stream waiting for credit.
+    writer: Option<Waker>,
+    // If there's a stream for the connection.
+    claimed: bool,
+    // If the connection was closed on our side.
+    closing: bool,
+    // If the close frame still needs to be sent.
+    close_pending: bool,
+    // If the connection was closed on the other side.
+    peer_closed: bool,
+}
+
+impl Inbox {
+    fn new() -> Self {
+        Self {
+            data: [0; INBOX_SIZE],
+            len: 0,
+            unacked: 0,
+            credit: INBOX_SIZE,
+            reader: None,
+            writer: None,
+            claimed: false,
+            closing: false,
+            close_pending: false,
+            peer_closed: false,
+        }
+    }
+
+    // Make the ID available again if the connection was closed on both sides.
+    fn reset_if_closed(&mut self) {
+        if self.closing && !self.close_pending && self.peer_closed {
+            *self = Self::new();
+        }
+    }
+}
+
+fn wake(waker: Option<Waker>) {
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+async fn read_exact<R, ReplyError>(reader: &mut R, mut buf: &mut [u8]) -> Result<(), ReplyError>
+where
+    R: Socket,
+{
+    while !buf.is_empty() {
+        match reader.read(buf).await? {
+            0 => return Err(crate::Error::SocketRead),
+            n => buf = &mut buf[n..],
+        }
+    }
+
+    Ok(())
+}
+
+const HEADER_SIZE: usize = 3;
+// The maximum payload of the frames we send. Keep it small, so that connections get their turn.
+const FRAME_PAYLOAD_SIZE: usize = 256;
+// The size of the buffer of each connection, and hence the initial credit.
+const INBOX_SIZE: usize = 512;
+const MAX_CONNECTIONS: usize = u8::MAX as usize + 1;
+// Set in the length field of credit frames.
+const CREDIT_FLAG: u16 = 0x8000;
+const MAX_CREDIT: usize = !CREDIT_FLAG as usize;
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::{future::Future, num::NonZeroUsize, pin::pin, task::Poll};
+
+    use futures_executor::block_on;
+    use futures_util::{
+        future::{join, join3, poll_fn, select, Either},
+        pin_mut,
+    };
+
+    use super::*;
+    use crate::connection::Channel;
+
+    #[test]
+    fn multiplexing() {
+        // One channel for each direction.
+        let (to_host, to_device) = (Channel::new(), Channel::new());
+        to_device.set_max_read_size(NonZeroUsize::new(5));
+        let (device_writer, host_reader) = to_host.split();
+        let (host_writer, device_reader) = to_device.split();
+        let device = Multiplexer::<_, 2>::new(device_writer);
+        let host = Multiplexer::<_, 2>::new(host_writer);
+
+        let test = async {
+            let mut host0 = host.stream(0).unwrap();
+            let mut host1 = host.stream(1).unwrap();
+            assert!(host.stream(1).is_none());
+            assert!(host.stream(2).is_none());
+            let mut device0 = device.stream(0).unwrap();
+            let mut device1 = device.stream(1).unwrap();
+
+            // More than fits in the inbox, while the other connection is used.
+            let large = [0x42; INBOX_SIZE * 3];
+            let send_large = async { host1.write::<&'static str>(&large).await.unwrap() };
+            let receive_large = async {
+                let mut received = 0;
+                let mut buf = [0; 100];
+                while received < large.len() {
+                    let n = device1.read::<&'static str>(&mut buf).await.unwrap();
+                    assert!(buf[..n].iter().all(|b| *b == 0x42));
+                    received += n;
+                }
+            };
+            let echo = async {
+                host0.write::<&'static str>(b"hello").await.unwrap();
+                let mut buf = [0; 5];
+                read_exact::<_, &'static str>(&mut device0, &mut buf)
+                    .await
+                    .unwrap();
+                device0.write::<&'static str>(&buf).await.unwrap();
+                let mut reply = [0; 5];
+                read_exact::<_, &'static str>(&mut host0, &mut reply)
+                    .await
+                    .unwrap();
+                assert_eq!(&reply, b"hello");
+            };
+            join3(send_large, receive_large, echo).await;
+        };
+
+        run(&host, host_reader, &device, device_reader, test);
+    }
+
+    #[test]
+    fn unclaimed() {
+        let (to_host, to_device) = (Channel::new(), Channel::new());
+        let (device_writer, host_reader) = to_host.split();
+        let (host_writer, device_reader) = to_device.split();
+        let device = Multiplexer::<_, 2>::new(device_writer);
+        let host = Multiplexer::<_, 2>::new(host_writer);
+
+        let test = async {
+            // The data sent before the device creates the connection isn't lost.
+            let mut host0 = host.stream(0).unwrap();
+            host0.write::<&'static str>(b"early").await.unwrap();
+            eventually(|| device.with_inbox(0, |inbox| inbox.len) == Some(5)).await;
+
+            let mut device0 = device.stream(0).unwrap();
+            let mut buf = [0; 5];
+            read_exact::<_, &'static str>(&mut device0, &mut buf)
+                .await
+                .unwrap();
+            assert_eq!(&buf, b"early");
+        };
+
+        run(&host, host_reader, &device, device_reader, test);
+    }
+
+    #[test]
+    fn flow_control() {
+        let (to_host, to_device) = (Channel::new(), Channel::new());
+        let (device_writer, host_reader) = to_host.split();
+        let (host_writer, device_reader) = to_device.split();
+        let device = Multiplexer::<_, 2>::new(device_writer);
+        let host = Multiplexer::<_, 2>::new(host_writer);
+
+        let test = async {
+            let mut host0 = host.stream(0).unwrap();
+            let mut host1 = host.stream(1).unwrap();
+            let mut device0 = device.stream(0).unwrap();
+
+            // No one reads connection 1 yet, so its writer runs out of credit...
+            let large = [0x42; INBOX_SIZE * 3];
+            let send_large = pin!(host1.write::<&'static str>(&large));
+            // ...while connection 0 keeps working.
+            let echo = pin!(async {
+                host0.write::<&'static str>(b"hello").await.unwrap();
+                let mut buf = [0; 5];
+                read_exact::<_, &'static str>(&mut device0, &mut buf)
+                    .await
+                    .unwrap();
+                assert_eq!(&buf, b"hello");
+            });
+            let Either::Right(((), send_large)) = select(send_large, echo).await else {
+                panic!("sent more than the credit");
+            };
+            assert_eq!(device.with_inbox(1, |inbox| inbox.len), Some(INBOX_SIZE));
+
+            // Reading grants the credit for the rest.
+            let mut device1 = device.stream(1).unwrap();
+            let receive_large = async {
+                let mut buf = [0; INBOX_SIZE * 3];
+                read_exact::<_, &'static str>(&mut device1, &mut buf)
+                    .await
+                    .unwrap();
+                assert!(buf.iter().all(|b| *b == 0x42));
+            };
+            let (sent, ()) = join(send_large, receive_large).await;
+            sent.unwrap();
+        };
+
+        run(&host, host_reader, &device, device_reader, test);
+    }
+
+    #[test]
+    fn close() {
+        let (to_host, to_device) = (Channel::new(), Channel::new());
+        let (device_writer, host_reader) = to_host.split();
+        let (host_writer, device_reader) = to_device.split();
+        let device = Multiplexer::<_, 2>::new(device_writer);
+        let host = Multiplexer::<_, 2>::new(host_writer);
+
+        let test = async {
+            let mut host0 = host.stream(0).unwrap();
+            let mut device0 = device.stream(0).unwrap();
+            host0.write::<&'static str>(b"bye").await.unwrap();
+            drop(host0);
+
+            // The data sent before closing is still read, followed by the end of the stream.
+            let mut buf = [0; 3];
+            read_exact::<_, &'static str>(&mut device0, &mut buf)
+                .await
+                .unwrap();
+            assert_eq!(&buf, b"bye");
+            assert_eq!(device0.read::<&'static str>(&mut buf).await.unwrap(), 0);
+            assert!(device0.write::<&'static str>(b"hello").await.is_err());
+
+            // The ID can't be reused until both sides closed the connection.
+            assert!(host.stream(0).is_none());
+            drop(device0);
+            eventually(|| host.with_inbox(0, |inbox| inbox.closing) == Some(false)).await;
+            eventually(|| device.with_inbox(0, |inbox| inbox.closing) == Some(false)).await;
+
+            let mut host0 = host.stream(0).unwrap();
+            let mut device0 = device.stream(0).unwrap();
+            device0.write::<&'static str>(b"again").await.unwrap();
+            let mut buf = [0; 5];
+            read_exact::<_, &'static str>(&mut host0, &mut buf)
+                .await
+                .unwrap();
+            assert_eq!(&buf, b"again");
+        };
+
+        run(&host, host_reader, &device, device_reader, test);
+    }
+
+    // Run `test` while both multiplexers are running.
+    fn run<W, R>(
+        host: &Multiplexer<W, 2>,
+        host_reader: R,
+        device: &Multiplexer<W, 2>,
+        device_reader: R,
+        test: impl Future<Output = ()>,
+    ) where
+        W: Socket,
+        R: Socket,
+    {
+        let run = join(
+            device.run::<_, &'static str>(device_reader),
+            host.run::<_, &'static str>(host_reader),
+        );
+        block_on(async {
+            pin_mut!(run, test);
+            if let Either::Left(((device, host), _)) = select(run, test).await {
+                panic!("multiplexer exited: {device:?}, {host:?}");
+            }
+        });
+    }
+
+    // Yield to the multiplexers until `condition` is true.
+    async fn eventually(mut condition: impl FnMut() -> bool) {
+        while !condition() {
+            let mut yielded = false;
+            poll_fn(|cx| {
+                if yielded {
+                    return Poll::Ready(());
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+
+                Poll::Pending
+            })
+            .await;
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use core::fmt::{self, Write};
+
+use serde::{
+    de::{
+        value::{Error as ValueError, MapDeserializer},
+        Deserializer, IgnoredAny, IntoDeserializer, MapAccess, Visitor,
+    },
+    Deserialize,
+};
+
+use crate::{
+    connection::{from_slice, Call},
+    varlink_service::{self, Info},
+};
+
+// The error to reply with to a call to `method` that the service couldn't deserialize.
+//
+// `M` is the method call type of the service and `buffer` the whole method call message.
+pub(super) fn error<'de, M>(
+    buffer: &'de [u8],
+    method: &'de str,
+    info: &Info<'_>,
+) -> varlink_service::Error
+where
+    M: Deserialize<'de>,
+{
+    // Deserialize the method without any parameters, which tells us if the service has it at all
+    // and the first parameter it requires.
+    let probe = M::deserialize(MapDeserializer::<_, ValueError>::new(
+        [
+            ("method", ProbeValue::Str(method)),
+            ("parameters", ProbeValue::EmptyMap),
+        ]
+        .into_iter(),
+    ))
+    .err();
+    if let Some(e) = &probe {
+        if quoted(e, "unknown variant `").is_some() {
+            return method_error(method, info);
+        }
+    }
+
+    // The method exists so it's the parameters that are invalid. If the deserializer doesn't tell
+    // which one, we go for the first one required but missing, or the first one passed.
+    let names = from_slice::<CallParameters<'_>, &'static str>(buffer)
+        .ok()
+        .and_then(|call| call.parameters)
+        .unwrap_or_default();
+    let parameter = from_slice::<Call<M>, &'static str>(buffer)
+        .err()
+        .and_then(|e| quoted(&e, "missing field `").or_else(|| quoted(&e, "unknown field `")))
+        .or_else(|| {
+            probe
+                .and_then(|e| quoted(&e, "missing field `"))
+                .filter(|name| !names.contains(name))
+        })
+        .or_else(|| names.first().map(Into::into))
+        // The parameters aren't even an object.
+        .unwrap_or_else(|| "parameters".into());
+
+    varlink_service::Error::InvalidParameter { parameter }
+}
+
+// The error to reply with to a call to `method`, which the service doesn't have.
+fn method_error(method: &str, info: &Info<'_>) -> varlink_service::Error {
+    let Some((interface, name)) = method.rsplit_once('.') else {
+        return varlink_service::Error::MethodNotFound {
+            method: method.into(),
+        };
+    };
+
+    match info.description(interface) {
+        Some(description) if varlink_service::declares_method(description, name) => {
+            varlink_service::Error::MethodNotImplemented {
+                method: method.into(),
+            }
+        }
+        // Without any interface descriptions, we can't know if the interface is implemented.
+        None if !info.descriptions.is_empty() => varlink_service::Error::InterfaceNotFound {
+            interface: interface.into(),
+        },
+        _ => varlink_service::Error::MethodNotFound {
+            method: method.into(),
+        },
+    }
+}
+
+// The name quoted after `prefix` in the message of `error`, as in the errors of `serde`.
+fn quoted(error: &impl fmt::Display, prefix: &str) -> Option<varlink_service::Name> {
+    let mut message = Message {
+        buf: [0; MESSAGE_MAX_LEN],
+        len: 0,
+    };
+    let _ = write!(message, "{error}");
+    let message = message.as_str();
+    let start = message.find(prefix)? + prefix.len();
+    let len = message[start..].find('`')?;
+
+    Some(message[start..start + len].into())
+}
+
+// An error message formatted on the stack, truncated if it doesn't fit.
+struct Message {
+    buf: [u8; MESSAGE_MAX_LEN],
+    len: usize,
+}
+
+impl Message {
+    fn as_str(&self) -> &str {
+        let bytes = &self.buf[..self.len];
+        match core::str::from_utf8(bytes) {
+            Ok(message) => message,
+            // Truncated in the middle of a character.
+            Err(e) => core::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+        }
+    }
+}
+
+impl Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+
+        Ok(())
+    }
+}
+
+// Enough for the messages about the longest Varlink names.
+const MESSAGE_MAX_LEN: usize = 512;
+
+// A value of the method call used to probe the method call type of the service.
+enum ProbeValue<'a> {
+    Str(&'a str),
+    EmptyMap,
+}
+
+impl<'de> Deserializer<'de> for ProbeValue<'de> {
+    type Error = ValueError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, ValueError>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            ProbeValue::Str(s) => visitor.visit_borrowed_str(s),
+            ProbeValue::EmptyMap => {
+                visitor.visit_map(MapDeserializer::new(core::iter::empty::<(&str, &str)>()))
+            }
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ValueError> for ProbeValue<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+// Only the parameter names of a method call.
+#[derive(Debug, Deserialize)]
+struct CallParameters<'m> {
+    #[serde(borrow)]
+    parameters: Option<ParameterNames<'m>>,
+}
+
+// The names of the parameters of a method call, as many as fit.
+#[derive(Debug, Default)]
+struct ParameterNames<'m> {
+    names: [&'m str; PARAMETERS_MAX],
+    len: usize,
+}
+
+impl ParameterNames<'_> {
+    fn first(&self) -> Option<&str> {
+        self.names[..self.len].first().copied()
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.names[..self.len].iter().any(|n| *n == name)
+    }
+}
+
+impl<'de> Deserialize<'de> for ParameterNames<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NamesVisitor;
+
+        impl<'de> Visitor<'de> for NamesVisitor {
+            type Value = ParameterNames<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("an object")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut names = ParameterNames::default();
+                while let Some(name) = map.next_key::<&str>()? {
+                    map.next_value::<IgnoredAny>()?;
+                    if names.len < PARAMETERS_MAX {
+                        names.names[names.len] = name;
+                        names.len += 1;
+                    }
+                }
+
+                Ok(names)
+            }
+        }
+
+        deserializer.deserialize_map(NamesVisitor)
+    }
+}
+
+const PARAMETERS_MAX: usize = 16;
@@ -0,0 +1,17 @@
+use core::future::Future;
+
+use crate::{connection::Socket, Connection};
+
+/// A listener is a server that listens for incoming connections.
+///
+/// This is the trait that needs to be implemented for a type to be used as a listener for the
+/// [`Server`](super::Server).
+pub trait Listener {
+    /// The type of the socket the connections this listener creates will use.
+    type Socket: Socket;
+
+    /// Accept a new connection.
+    fn accept<ReplyError>(
+        &mut self,
+    ) -> impl Future<Output = crate::Result<Connection<Self::Socket>, ReplyError>>;
+}
@@ -0,0 +1,486 @@
+//! Contains server related API.
+
+mod invalid_call;
+mod listener;
+pub use listener::Listener;
+mod service;
+pub use service::{MethodReply, Service};
+
+use serde::Deserialize;
+
+use crate::{
+    connection::{from_slice, Call, Socket},
+    varlink_service::{self, Info, InterfaceDescription},
+    Connection,
+};
+
+/// A server.
+///
+/// The server listens for incoming connections and handles method calls using a service. Method
+/// calls to the [`org.varlink.service`](crate::varlink_service) interface are handled by the server
+/// itself, using the [`Info`] it's created with.
+///
+/// Method calls that can't be deserialized are replied to with the appropriate
+/// [`org.varlink.service` error](varlink_service::Error): `InvalidParameter` if the service has the
+/// method, naming the offending parameter as far as it can be determined. Otherwise, based on the
+/// interface descriptions in [`Info`], `InterfaceNotFound` if the interface isn't implemented,
+/// `MethodNotImplemented` if the interface declares the method and `MethodNotFound` if it doesn't.
+#[derive(Debug)]
+pub struct Server<L> {
+    listener: L,
+    info: Info<'static>,
+}
+
+impl<L> Server<L>
+where
+    L: Listener,
+{
+    /// Create a new server that accepts connections from `listener`.
+    ///
+    /// The server replies to `org.varlink.service.GetInfo` calls with empty service information.
+    /// Use [`Server::with_info`] to provide it.
+    pub fn new(listener: L) -> Self {
+        Self::with_info(listener, Info::default())
+    }
+
+    /// Create a new server that accepts connections from `listener`, with service information.
+    ///
+    /// `info` is used to reply to `org.varlink.service` method calls. Its `descriptions` should
+    /// contain the IDL descriptions of all the interfaces implemented by the service.
+    pub fn with_info(listener: L, info: Info<'static>) -> Self {
+        Self { listener, info }
+    }
+
+    /// The service information.
+    pub fn info(&self) -> &Info<'static> {
+        &self.info
+    }
+
+    /// Run the server.
+    ///
+    /// The server accepts a connection from the listener and keeps handling method calls on it
+    /// using `service`, until the connection is closed or an error occurs on it. It then moves on
+    /// to accepting the next connection. This method only returns if accepting a connection fails.
+    pub async fn run<Srv, ReplyError>(&mut self, mut service: Srv) -> crate::Result<(), ReplyError>
+    where
+        Srv: Service,
+    {
+        loop {
+            let mut connection = self.listener.accept::<ReplyError>().await?;
+            while serve_next::<_, _, ReplyError>(&mut service, &mut connection, &self.info)
+                .await
+                .is_ok()
+            {}
+        }
+    }
+}
+
+/// Receive the next method call on the `connection`, handle it and send back the reply.
+///
+/// This is what [`Server`] does for each method call. It's useful for implementing servers with
+/// different connection handling, e.g one that serves connections concurrently. Method calls to
+/// the `org.varlink.service` interface are handled using `info`, all the other ones using
+/// `service`.
+pub async fn serve_next<Srv, Sock, ReplyError>(
+    service: &mut Srv,
+    connection: &mut Connection<Sock>,
+    info: &Info<'_>,
+) -> crate::Result<(), ReplyError>
+where
+    Srv: Service,
+    Sock: Socket,
+{
+    // Fetched before reading the message, as the call borrows the connection's buffer.
+    #[cfg(all(feature = "std", unix))]
+    let credentials = connection.peer_credentials::<ReplyError>().ok();
+    let message = connection.read_message::<ReplyError>().await?;
+    let buffer = message.bytes;
+    let call = match from_slice::<Call<ServerCall<'_, Srv::MethodCall<'_>>>, _>(buffer) {
+        Ok(call) => call,
+        Err(e) => {
+            // Reply with the appropriate error if we at least know which method was called.
+            let Ok(call) = from_slice::<Call<MethodName<'_>>, ReplyError>(buffer) else {
+                return Err(e);
+            };
+            let oneway = call.oneway().unwrap_or(false);
+            let error =
+                invalid_call::error::<Srv::MethodCall<'_>>(buffer, call.method().method, info);
+            if oneway {
+                return Ok(());
+            }
+
+            return connection.write_message(&error).await;
+        }
+    };
+    let oneway = call.oneway();
+    let more = call.more();
+    let upgrade = call.upgrade();
+    let method = match call.into_method() {
+        ServerCall::VarlinkService(method) => method,
+        ServerCall::Service(method) => {
+            let call = Call::new(method, oneway, more, upgrade);
+            #[cfg(all(feature = "std", unix))]
+            let call = {
+                let mut call = call;
+                call.set_peer_credentials(credentials);
+                call.set_fds(message.fds);
+                call
+            };
+            let reply = service.handle(call).await;
+            if oneway.unwrap_or(false) {
+                return Ok(());
+            }
+
+            return service::send_reply(connection, reply, more.unwrap_or(false)).await;
+        }
+    };
+    if oneway.unwrap_or(false) {
+        return Ok(());
+    }
+
+    match method {
+        varlink_service::Method::GetInfo => connection.send_reply(Some(*info), None).await,
+        varlink_service::Method::GetInterfaceDescription { interface } => {
+            match info.description(interface) {
+                Some(description) => {
+                    let reply = InterfaceDescription { description };
+                    connection.send_reply(Some(reply), None).await
+                }
+                None => {
+                    let error = varlink_service::Error::InterfaceNotFound {
+                        interface: interface.into(),
+                    };
+                    connection.write_message(&error).await
+                }
+            }
+        }
+    }
+}
+
+// A method call received by the server.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ServerCall<'m, M> {
+    // A call to the `org.varlink.service` interface, handled by the server itself.
+    VarlinkService(#[serde(borrow)] varlink_service::Method<'m>),
+    // A call to the service.
+    Service(M),
+}
+
+// Only the method name of a method call.
+#[derive(Debug, Deserialize)]
+struct MethodName<'m> {
+    method: &'m str,
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::{io, string::String, vec::Vec};
+
+    use futures_executor::block_on;
+    use futures_util::{future::join, stream};
+    use serde::Serialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::connection::{Channel, Endpoint, Reply};
+
+    #[test]
+    fn run() {
+        let (first, second) = (Channel::new(), Channel::new());
+        let (first_client, first_server) = first.split();
+        let (second_client, second_server) = second.split();
+        let listener = ChannelListener(vec![second_server, first_server]);
+        let mut server = Server::with_info(
+            listener,
+            Info {
+                product: "counter",
+                ..Info::default()
+            },
+        );
+
+        let clients = async {
+            // `varlinkctl` sends empty parameters to methods without any.
+            let mut client = Connection::new(first_client);
+            let reply = client
+                .call_method::<_, InfoReply, CounterError>(VarlinkCtl::GetInfo {})
+                .await
+                .unwrap();
+            assert_eq!(reply.parameters().unwrap().product, "counter");
+            let reply = client
+                .call_method::<_, Count, CounterError>(Method::Increment)
+                .await
+                .unwrap();
+            assert_eq!(reply.parameters().unwrap().count, 1);
+            // Closing the connection makes the server move on to the next one.
+            drop(client);
+
+            // The service is kept across connections. No reply is sent to oneway calls, so the
+            // next reply received is the one to the call following it.
+            let mut client = Connection::new(second_client);
+            client
+                .send_call::<_, CounterError>(Method::Increment, Some(true), None, None)
+                .await
+                .unwrap();
+            let reply = client
+                .call_method::<_, Count, CounterError>(Method::Increment)
+                .await
+                .unwrap();
+            assert_eq!(reply.parameters().unwrap().count, 3);
+        };
+        let (res, ()) = block_on(join(
+            server.run::<_, &'static str>(Counter::default()),
+            clients,
+        ));
+        // The server only returns when there are no more connections to accept.
+        match res {
+            Err(crate::Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted),
+            res => panic!("unexpected result: {res:?}"),
+        }
+    }
+
+    #[test]
+    fn replies() {
+        let channel = Channel::new();
+        let (client, server) = channel.split();
+        let (mut client, mut server) = (Connection::new(client), Connection::new(server));
+        let mut counter = Counter::default();
+        let info = Info::default();
+
+        block_on(async {
+            // The server sets the `continues` flag on all the replies but the last, regardless of
+            // what the service set.
+            for (to, expected) in [
+                (2, &[(Some(1), Some(true)), (Some(2), None)][..]),
+                // An empty stream still gets a final reply.
+                (0, &[(None, None)][..]),
+            ] {
+                let replies = async {
+                    let mut stream = client
+                        .call_method_more::<_, CounterError>(Method::Count { to })
+                        .await
+                        .unwrap();
+                    let mut replies = Vec::new();
+                    while let Some(reply) = stream.next::<Count, CounterError>().await {
+                        let reply = reply.unwrap();
+                        replies.push((reply.parameters().map(|c| c.count), reply.continues()));
+                    }
+                    assert!(stream.is_done());
+
+                    replies
+                };
+                let serve = serve_next::<_, _, &'static str>(&mut counter, &mut server, &info);
+                let (replies, served) = join(replies, serve).await;
+                served.unwrap();
+                assert_eq!(replies, expected);
+            }
+
+            // Without the `more` flag, only the first reply is sent, as a final one.
+            let reply = client.call_method::<_, Count, CounterError>(Method::Count { to: 2 });
+            let serve = serve_next::<_, _, &'static str>(&mut counter, &mut server, &info);
+            let (reply, served) = join(reply, serve).await;
+            served.unwrap();
+            let reply = reply.unwrap();
+            assert_eq!(reply.parameters().unwrap().count, 1);
+            assert_eq!(reply.continues(), None);
+
+            let reply = client.call_method::<_, Count, CounterError>(Method::Fail);
+            let serve = serve_next::<_, _, &'static str>(&mut counter, &mut server, &info);
+            let (reply, served) = join(reply, serve).await;
+            served.unwrap();
+            assert!(matches!(
+                reply,
+                Err(crate::Error::Reply(CounterError::Failed))
+            ));
+
+            // Calls to unknown methods are answered by the server.
+            let reply = client.call_method::<_, Count, CounterError>(Unknown::Reset);
+            let serve = serve_next::<_, _, &'static str>(&mut counter, &mut server, &info);
+            let (reply, served) = join(reply, serve).await;
+            served.unwrap();
+            match reply {
+                Err(crate::Error::VarlinkService(varlink_service::Error::MethodNotFound {
+                    method,
+                })) => assert_eq!(method, "org.example.counter.Reset"),
+                reply => panic!("unexpected reply: {reply:?}"),
+            }
+        });
+    }
+
+    #[test]
+    fn invalid_calls() {
+        let channel = Channel::new();
+        let (client, server) = channel.split();
+        let (mut client, mut server) = (Connection::new(client), Connection::new(server));
+        let mut counter = Counter::default();
+        let descriptions = [COUNTER];
+
+        block_on(async {
+            for (call, descriptions, expected) in [
+                // The invalid parameter is named, with or without interface descriptions.
+                (
+                    json!({"method": "org.example.counter.Count", "parameters": {}}),
+                    &[][..],
+                    varlink_service::Error::InvalidParameter {
+                        parameter: "to".into(),
+                    },
+                ),
+                (
+                    json!({"method": "org.example.counter.Count", "parameters": {"to": "2"}}),
+                    &descriptions[..],
+                    varlink_service::Error::InvalidParameter {
+                        parameter: "to".into(),
+                    },
+                ),
+                (
+                    json!({"method": "org.example.counter.Reset"}),
+                    &[][..],
+                    varlink_service::Error::MethodNotFound {
+                        method: "org.example.counter.Reset".into(),
+                    },
+                ),
+                (
+                    json!({"method": "org.example.counter.Reset"}),
+                    &descriptions[..],
+                    varlink_service::Error::MethodNotImplemented {
+                        method: "org.example.counter.Reset".into(),
+                    },
+                ),
+                (
+                    json!({"method": "org.example.timer.Start"}),
+                    &descriptions[..],
+                    varlink_service::Error::InterfaceNotFound {
+                        interface: "org.example.timer".into(),
+                    },
+                ),
+            ] {
+                let info = Info {
+                    descriptions,
+                    ..Info::default()
+                };
+                let reply = async {
+                    client
+                        .send_call::<_, CounterError>(call, None, None, None)
+                        .await
+                        .unwrap();
+                    client.receive_reply::<Count, CounterError>().await
+                };
+                let serve = serve_next::<_, _, &'static str>(&mut counter, &mut server, &info);
+                let (reply, served) = join(reply, serve).await;
+                served.unwrap();
+                match reply {
+                    Err(crate::Error::VarlinkService(e)) => assert_eq!(e, expected),
+                    reply => panic!("unexpected reply: {reply:?}"),
+                }
+            }
+        });
+    }
+
+    const COUNTER: &str = "interface org.example.counter\n\n\
+                           method Count(to: int) -> (count: int)\n\
+                           method Increment() -> (count: int)\n\
+                           method Reset() -> ()\n\
+                           method Fail() -> ()\n\
+                           error Failed ()\n";
+
+    // Hands out connections to the server, then fails.
+    #[derive(Debug)]
+    struct ChannelListener<'c>(Vec<Endpoint<'c>>);
+
+    impl<'c> Listener for ChannelListener<'c> {
+        type Socket = Endpoint<'c>;
+
+        async fn accept<ReplyError>(
+            &mut self,
+        ) -> crate::Result<Connection<Endpoint<'c>>, ReplyError> {
+            self.0
+                .pop()
+                .map(Connection::new)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::ConnectionAborted).into())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Counter {
+        count: i64,
+    }
+
+    impl Service for Counter {
+        type MethodCall<'de> = Method;
+        type Reply<'ser> = Count;
+        type ReplyStream = stream::Iter<std::vec::IntoIter<Reply<Count>>>;
+        type ReplyStreamParams = Count;
+        type ReplyError<'ser> = CounterError;
+
+        async fn handle<'ser>(
+            &'ser mut self,
+            call: Call<Method>,
+        ) -> MethodReply<Count, Self::ReplyStream, CounterError> {
+            match call.into_method() {
+                Method::Count { to } => {
+                    let replies =
+                        (1..=to).map(|count| Reply::new(Some(Count { count }), Some(true)));
+
+                    MethodReply::Multi(stream::iter(replies.collect::<Vec<_>>()))
+                }
+                Method::Increment => {
+                    self.count += 1;
+
+                    MethodReply::Single(Some(Count { count: self.count }))
+                }
+                Method::Fail => MethodReply::Error(CounterError::Failed),
+            }
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "method", content = "parameters")]
+    enum Method {
+        #[serde(rename = "org.example.counter.Count")]
+        Count { to: i64 },
+        #[serde(
+            rename = "org.example.counter.Increment",
+            deserialize_with = "crate::connection::empty_parameters"
+        )]
+        Increment,
+        #[serde(
+            rename = "org.example.counter.Fail",
+            deserialize_with = "crate::connection::empty_parameters"
+        )]
+        Fail,
+    }
+
+    #[derive(Debug, Serialize)]
+    #[serde(tag = "method", content = "parameters")]
+    enum Unknown {
+        #[serde(rename = "org.example.counter.Reset")]
+        Reset,
+    }
+
+    #[derive(Debug, Serialize)]
+    #[serde(tag = "method", content = "parameters")]
+    enum VarlinkCtl {
+        #[serde(rename = "org.varlink.service.GetInfo")]
+        GetInfo {},
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Count {
+        count: i64,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "error", content = "parameters")]
+    enum CounterError {
+        #[serde(
+            rename = "org.example.counter.Failed",
+            deserialize_with = "crate::connection::empty_parameters"
+        )]
+        Failed,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct InfoReply {
+        product: String,
+    }
+}
@@ -0,0 +1,165 @@
+use core::{fmt::Debug, future::Future};
+#[cfg(all(feature = "std", unix))]
+use std::os::fd::AsFd;
+
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    connection::{Call, Reply, Socket},
+    varlink_service::Info,
+    Connection,
+};
+
+/// Service trait for handling method calls.
+///
+/// This is the trait that needs to be implemented for a type to be passed to [`Server::run`].
+///
+/// [`Server::run`]: super::Server::run
+pub trait Service {
+    /// The type of method call that this service handles.
+    ///
+    /// This should be a type that can deserialize itself from a complete method call message, i-e
+    /// an object containing `method` and `parameter` fields. This can be easily achieved using the
+    /// `serde::Deserialize` derive (See the code snippet in [`Connection::send_call`]
+    /// documentation for an example).
+    type MethodCall<'de>: Deserialize<'de> + Debug;
+    /// The type of the successful reply.
+    ///
+    /// This should be a type that can serialize itself as the `parameters` field of the reply.
+    type Reply<'ser>: Serialize + Debug
+    where
+        Self: 'ser;
+    /// The type of the stream of replies, for method calls with the `more` flag set.
+    ///
+    /// The `continues` flag of the replies is set by the server, based on whether more replies
+    /// follow in the stream, so whatever the service sets is ignored. Services that don't send
+    /// multiple replies can use [`futures_util::stream::Empty`].
+    type ReplyStream: Stream<Item = Reply<Self::ReplyStreamParams>> + Unpin;
+    /// The type of the parameters of each reply in [`Service::ReplyStream`].
+    type ReplyStreamParams: Serialize + Debug;
+    /// The type of the error reply.
+    ///
+    /// This should be a type that can serialize itself to the whole reply object, containing
+    /// `error` and `parameter` fields. This can be easily achieved using the `serde::Serialize`
+    /// derive (See the code snippet in [`Connection::receive_reply`] documentation for an example).
+    type ReplyError<'ser>: Serialize + Debug
+    where
+        Self: 'ser;
+
+    /// Handle a method call.
+    fn handle<'ser>(
+        &'ser mut self,
+        call: Call<Self::MethodCall<'_>>,
+    ) -> impl Future<Output = MethodReply<Self::Reply<'ser>, Self::ReplyStream, Self::ReplyError<'ser>>>;
+
+    /// Receive the next method call on the `connection`, handle it and send back the reply.
+    ///
+    /// This is the same as [`serve_next`] with empty service information.
+    ///
+    /// [`serve_next`]: super::serve_next
+    fn handle_next<Sock, ReplyError>(
+        &mut self,
+        connection: &mut Connection<Sock>,
+    ) -> impl Future<Output = crate::Result<(), ReplyError>>
+    where
+        Self: Sized,
+        Sock: Socket,
+    {
+        async move { super::serve_next(self, connection, &Info::default()).await }
+    }
+}
+
+// Send the `reply` of a service to a method call that had the `more` flag set to `more`.
+pub(super) async fn send_reply<Sock, Params, S, StreamParams, Error, ReplyError>(
+    connection: &mut Connection<Sock>,
+    reply: MethodReply<Params, S, Error>,
+    more: bool,
+) -> crate::Result<(), ReplyError>
+where
+    Sock: Socket,
+    Params: Serialize + Debug,
+    S: Stream<Item = Reply<StreamParams>> + Unpin,
+    StreamParams: Serialize + Debug,
+    Error: Serialize + Debug,
+{
+    let mut stream = match reply {
+        MethodReply::Single(params) => return connection.send_reply(params, None).await,
+        #[cfg(all(feature = "std", unix))]
+        MethodReply::SingleWithFds(params, fds) => {
+            let fds = fds.iter().map(AsFd::as_fd).collect::<std::vec::Vec<_>>();
+
+            return connection.send_reply_with_fds(params, None, &fds).await;
+        }
+        MethodReply::Error(error) => return connection.write_message(&error).await,
+        MethodReply::Multi(stream) => stream,
+    };
+    let Some(mut reply) = stream.next().await else {
+        // The sequence still needs a final reply.
+        return connection.send_reply::<StreamParams, _>(None, None).await;
+    };
+    if !more {
+        return send_stream_reply(connection, reply, false).await;
+    }
+
+    // Each reply is only sent once we know if another one follows it, so that the last one can be
+    // sent without the `continues` flag.
+    loop {
+        let next = stream.next().await;
+        send_stream_reply(connection, reply, next.is_some()).await?;
+        match next {
+            Some(next) => reply = next,
+            None => return Ok(()),
+        }
+    }
+}
+
+// Send a `reply` from a stream, along with its file descriptors.
+async fn send_stream_reply<Sock, Params, ReplyError>(
+    connection: &mut Connection<Sock>,
+    reply: Reply<Params>,
+    continues: bool,
+) -> crate::Result<(), ReplyError>
+where
+    Sock: Socket,
+    Params: Serialize + Debug,
+{
+    let continues = Some(continues).filter(|c| *c);
+    #[cfg(all(feature = "std", unix))]
+    {
+        let mut reply = reply;
+        let fds = reply.take_fds();
+        let fds = fds.iter().map(AsFd::as_fd).collect::<std::vec::Vec<_>>();
+
+        connection
+            .send_reply_with_fds(reply.into_parameters(), continues, &fds)
+            .await
+    }
+    #[cfg(not(all(feature = "std", unix)))]
+    connection
+        .send_reply(reply.into_parameters(), continues)
+        .await
+}
+
+/// The reply of a [`Service`] to a method call.
+#[derive(Debug)]
+pub enum MethodReply<Params, ReplyStream, ReplyError> {
+    /// A single successful reply.
+    Single(Option<Params>),
+    /// A single successful reply, with file descriptors passed along with it.
+    ///
+    /// The reply parameters can refer to the file descriptors by their index, using
+    /// [`FdIndex`](crate::connection::FdIndex). File descriptors can be passed along with the
+    /// replies of [`MethodReply::Multi`] through [`Reply::set_fds`].
+    #[cfg(all(feature = "std", unix))]
+    SingleWithFds(Option<Params>, std::vec::Vec<std::os::fd::OwnedFd>),
+    /// An error reply.
+    Error(ReplyError),
+    /// Multiple successful replies.
+    ///
+    /// The service should only reply with this variant if the method call has the `more` flag set.
+    /// The server sets the `continues` flag on all the replies but the last one. Hence each reply
+    /// is only sent once the stream yields the next one or ends. If the stream is empty, a single
+    /// empty reply is sent.
+    Multi(ReplyStream),
+}
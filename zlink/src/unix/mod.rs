@@ -0,0 +1,180 @@
+//! Helpers for implementing the Unix Domain Socket transport.
+//!
+//! These are meant for the crates providing the transport for a specific runtime, which only need
+//! to wrap them into the asynchronous API of the runtime.
+
+mod socket_file;
+pub use socket_file::SocketFile;
+
+use core::mem::MaybeUninit;
+use std::{
+    io::{self, IoSlice, IoSliceMut},
+    os::fd::{AsFd, BorrowedFd, OwnedFd},
+    vec,
+    vec::Vec,
+};
+
+use rustix::net::{
+    recvmsg, sendmsg, RecvAncillaryBuffer, RecvAncillaryMessage, RecvFlags, ReturnFlags,
+    SendAncillaryBuffer, SendAncillaryMessage, SendFlags,
+};
+
+/// Receive data from `socket` into `buf`, along with the file descriptors passed with it.
+///
+/// The received file descriptors are appended to `fds` and are close-on-exec. This fails if the
+/// peer passed more file descriptors than can be received at once, as the data would then be
+/// received without some of its file descriptors. This doesn't wait for the socket to be readable,
+/// so it fails with [`io::ErrorKind::WouldBlock`] on a non-blocking socket that has no data
+/// available.
+pub fn recv_with_fds<S>(socket: S, buf: &mut [u8], fds: &mut Vec<OwnedFd>) -> io::Result<usize>
+where
+    S: AsFd,
+{
+    let mut space = [MaybeUninit::uninit(); rustix::cmsg_space!(ScmRights(MAX_FDS))];
+    let mut control = RecvAncillaryBuffer::new(&mut space);
+    let mut iov = [IoSliceMut::new(buf)];
+    let msg = recvmsg(socket, &mut iov, &mut control, RECV_FLAGS)?;
+    let mut received_fds = Vec::new();
+    for message in control.drain() {
+        if let RecvAncillaryMessage::ScmRights(received) = message {
+            for fd in received {
+                #[cfg(target_vendor = "apple")]
+                rustix::io::fcntl_setfd(&fd, rustix::io::FdFlags::CLOEXEC)?;
+                received_fds.push(fd);
+            }
+        }
+    }
+    if msg.flags.contains(ReturnFlags::CTRUNC) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "too many file descriptors received",
+        ));
+    }
+    fds.extend(received_fds);
+
+    Ok(msg.bytes)
+}
+
+/// Send data from `buf` to `socket`, passing `fds` along with it.
+///
+/// Returns the number of bytes written, which may be less than the length of `buf`. The file
+/// descriptors are passed along with the written bytes so the rest of `buf` must be sent without
+/// them. Like [`recv_with_fds`], this doesn't wait for the socket to be writable.
+pub fn send_with_fds<S>(socket: S, buf: &[u8], fds: &[BorrowedFd<'_>]) -> io::Result<usize>
+where
+    S: AsFd,
+{
+    let mut space = vec![MaybeUninit::uninit(); rustix::cmsg_space!(ScmRights(fds.len()))];
+    let mut control = SendAncillaryBuffer::new(&mut space);
+    if !control.push(SendAncillaryMessage::ScmRights(fds)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "too many file descriptors",
+        ));
+    }
+
+    sendmsg(socket, &[IoSlice::new(buf)], &mut control, SEND_FLAGS).map_err(Into::into)
+}
+
+/// Get a pidfd referring to the peer process of `socket`.
+///
+/// The pidfd is fetched through `SO_PEERPIDFD`, so it refers to the process that connected the
+/// socket, even if that process already exited and its process ID got reused. This is only
+/// supported since Linux 6.5, returning `None` on older kernels. Opening a pidfd from the process
+/// ID would be racy.
+#[cfg(target_os = "linux")]
+pub fn peer_pidfd<S>(socket: S) -> Option<OwnedFd>
+where
+    S: AsFd,
+{
+    use core::{ffi::c_int, mem::size_of};
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    let mut pidfd: c_int = -1;
+    let mut len = size_of::<c_int>() as libc::socklen_t;
+    // SAFETY: The socket is valid for the duration of the call and the option value is written to
+    // a valid location of the given size.
+    let res = unsafe {
+        libc::getsockopt(
+            socket.as_fd().as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERPIDFD,
+            (&mut pidfd as *mut c_int).cast(),
+            &mut len,
+        )
+    };
+    if res != 0 || pidfd < 0 {
+        return None;
+    }
+
+    // SAFETY: The kernel created the pidfd for us to own.
+    Some(unsafe { OwnedFd::from_raw_fd(pidfd) })
+}
+
+// The maximum number of file descriptors that can be passed along with a message on Linux.
+const MAX_FDS: usize = 253;
+
+// Apple systems don't support `MSG_CMSG_CLOEXEC` so there, the received file descriptors are made
+// close-on-exec right after receiving them instead.
+#[cfg(not(target_vendor = "apple"))]
+const RECV_FLAGS: RecvFlags = RecvFlags::CMSG_CLOEXEC;
+#[cfg(target_vendor = "apple")]
+const RECV_FLAGS: RecvFlags = RecvFlags::empty();
+
+// Apple systems don't support `MSG_NOSIGNAL` either. Like for the other writes, we rely on Rust
+// programs ignoring `SIGPIPE` by default there.
+#[cfg(not(target_vendor = "apple"))]
+const SEND_FLAGS: SendFlags = SendFlags::NOSIGNAL;
+#[cfg(target_vendor = "apple")]
+const SEND_FLAGS: SendFlags = SendFlags::empty();
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixStream,
+    };
+
+    use super::*;
+
+    #[test]
+    fn fds() {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let (mut pipe_read, pipe_write) = UnixStream::pair().unwrap();
+
+        let written = send_with_fds(&ours, b"hello", &[pipe_write.as_fd()]).unwrap();
+        assert_eq!(written, 5);
+        drop(pipe_write);
+
+        let mut buf = [0; 16];
+        let mut fds = Vec::new();
+        let read = recv_with_fds(&theirs, &mut buf, &mut fds).unwrap();
+        assert_eq!(&buf[..read], b"hello");
+        assert_eq!(fds.len(), 1);
+
+        let mut stream = UnixStream::from(fds.pop().unwrap());
+        stream.write_all(b"world").unwrap();
+        drop(stream);
+        let mut received = std::string::String::new();
+        pipe_read.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "world");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn peer_pidfd() {
+        let (ours, _theirs) = UnixStream::pair().unwrap();
+
+        // Not supported before Linux 6.5.
+        let Some(pidfd) = super::peer_pidfd(&ours) else {
+            return;
+        };
+        let fdinfo = std::fs::read_to_string(std::format!(
+            "/proc/self/fdinfo/{}",
+            std::os::fd::AsRawFd::as_raw_fd(&pidfd)
+        ))
+        .unwrap();
+        let pid = std::format!("Pid:\t{}", std::process::id());
+        assert!(fdinfo.lines().any(|line| line == pid));
+    }
+}
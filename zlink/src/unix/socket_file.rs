@@ -0,0 +1,206 @@
+use std::{
+    ffi::OsString,
+    format,
+    fs::{self, DirBuilder, Permissions},
+    io,
+    os::unix::fs::{DirBuilderExt, FileTypeExt},
+    path::{Path, PathBuf},
+};
+
+/// The file of a Unix Domain Socket a listener is bound to.
+///
+/// This takes care of the file for the Unix Domain Socket listener implementations: a stale socket
+/// file is removed before binding and the file is removed again when this is dropped.
+#[derive(Debug)]
+pub struct SocketFile {
+    path: PathBuf,
+}
+
+impl SocketFile {
+    /// Bind a listener to the socket file at `path`, using `bind`.
+    ///
+    /// If a socket file already exists at `path` but no one is listening on it anymore, the file is
+    /// removed first. Returns the listener created by `bind`, along with the socket file.
+    pub fn bind<L, F>(path: &Path, bind: F) -> io::Result<(L, Self)>
+    where
+        F: FnOnce(&Path) -> io::Result<L>,
+    {
+        remove_stale_socket(path)?;
+        let listener = bind(path)?;
+
+        Ok((
+            listener,
+            Self {
+                path: path.to_path_buf(),
+            },
+        ))
+    }
+
+    /// Bind a listener to the socket file at `path`, using `bind`, with the given permissions.
+    ///
+    /// Clients need write permission on the socket to be able to connect to it. Unlike setting the
+    /// permissions after [`SocketFile::bind`], this ensures no client can connect to the socket
+    /// before it has the given permissions: the listener is bound in a private directory and the
+    /// socket file is only linked to `path` afterwards.
+    pub fn bind_with_permissions<L, F>(
+        path: &Path,
+        permissions: Permissions,
+        bind: F,
+    ) -> io::Result<(L, Self)>
+    where
+        F: FnOnce(&Path) -> io::Result<L>,
+    {
+        remove_stale_socket(path)?;
+        let dir = PrivateDir::create(path)?;
+        let private_path = dir.path.join("socket");
+        let listener = bind(&private_path)?;
+        fs::set_permissions(&private_path, permissions)?;
+        // Unlike renaming, linking doesn't replace whatever file may have been created at `path`
+        // in the meantime.
+        fs::hard_link(&private_path, path)?;
+
+        Ok((
+            listener,
+            Self {
+                path: path.to_path_buf(),
+            },
+        ))
+    }
+
+    /// The path of the socket file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        // Nothing we can do about a failure here.
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// A directory only accessible by us, next to a socket file, removed with its content on drop.
+struct PrivateDir {
+    path: PathBuf,
+}
+
+impl PrivateDir {
+    fn create(socket_path: &Path) -> io::Result<Self> {
+        let name = socket_path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "socket path has no file name")
+        })?;
+        let mut dir_name = OsString::from(format!(".{}.", std::process::id()));
+        dir_name.push(name);
+        let path = socket_path.with_file_name(dir_name);
+        DirBuilder::new().mode(0o700).create(&path)?;
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PrivateDir {
+    fn drop(&mut self) {
+        // Nothing we can do about a failure here.
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+// Remove the socket file at `path` if it exists and no one is listening on it.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if !metadata.file_type().is_socket() {
+        // Not ours to remove. Binding will fail with the appropriate error.
+        return Ok(());
+    }
+
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "another process is listening on the socket",
+        )),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        format,
+        os::unix::{fs::PermissionsExt, net::UnixListener},
+    };
+
+    use super::*;
+
+    #[test]
+    fn bind() {
+        let path = std::env::temp_dir().join(format!("zlink-{}.sock", std::process::id()));
+
+        // A stale socket file.
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let (listener, file) = SocketFile::bind(&path, |p| UnixListener::bind(p)).unwrap();
+        assert_eq!(file.path(), path);
+
+        // Can't bind while someone is listening.
+        let e = SocketFile::bind(&path, |p| UnixListener::bind(p)).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AddrInUse);
+        assert!(path.exists());
+
+        drop(listener);
+        drop(file);
+        assert!(!path.exists());
+
+        // Other files are left alone.
+        fs::write(&path, "").unwrap();
+        assert!(SocketFile::bind(&path, |p| UnixListener::bind(p)).is_err());
+        assert!(path.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bind_with_permissions() {
+        let path = std::env::temp_dir().join(format!("zlink-mode-{}.sock", std::process::id()));
+        let permissions = Permissions::from_mode(0o600);
+
+        let (listener, file) = SocketFile::bind_with_permissions(&path, permissions.clone(), |p| {
+            // Only we can access the socket until it's in place.
+            let dir = fs::metadata(p.parent().unwrap()).unwrap();
+            assert_eq!(dir.permissions().mode() & 0o777, 0o700);
+
+            UnixListener::bind(p)
+        })
+        .unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        std::os::unix::net::UnixStream::connect(&path).unwrap();
+        // The private directory is gone.
+        let dir = path.with_file_name(format!(
+            ".{}.zlink-mode-{}.sock",
+            std::process::id(),
+            std::process::id()
+        ));
+        assert!(!dir.exists());
+
+        drop(listener);
+        drop(file);
+        assert!(!path.exists());
+
+        // Other files are left alone.
+        fs::write(&path, "").unwrap();
+        assert!(
+            SocketFile::bind_with_permissions(&path, permissions, |p| UnixListener::bind(p))
+                .is_err()
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"");
+        fs::remove_file(&path).unwrap();
+    }
+}
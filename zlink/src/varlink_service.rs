@@ -0,0 +1,467 @@
+//! Contains the API of the [`org.varlink.service`][service] interface.
+//!
+//! Every Varlink service is required to implement this interface. [`Server`](crate::Server)
+//! implements it automatically, using the [`Info`] it's created with.
+//!
+//! [service]: https://varlink.org/Service
+
+use serde::{
+    de::Visitor,
+    ser::{SerializeSeq, SerializeStruct},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// The name of the interface.
+pub const INTERFACE_NAME: &str = "org.varlink.service";
+
+/// The IDL description of the interface.
+pub const DESCRIPTION: &str = r#"# The Varlink Service Interface is provided by every varlink service. It
+# describes the service and the interfaces it implements.
+interface org.varlink.service
+
+# Get a list of all the interfaces a service provides and information
+# about the implementation.
+method GetInfo() -> (
+  vendor: string,
+  product: string,
+  version: string,
+  url: string,
+  interfaces: []string
+)
+
+# Get the description of an interface that is implemented by this service.
+method GetInterfaceDescription(interface: string) -> (description: string)
+
+# The requested interface was not found.
+error InterfaceNotFound (interface: string)
+
+# The requested method was not found
+error MethodNotFound (method: string)
+
+# The interface defines the requested method, but the service does not
+# implement it.
+error MethodNotImplemented (method: string)
+
+# One of the passed parameters is invalid.
+error InvalidParameter (parameter: string)
+
+# Client is denied access
+error PermissionDenied ()
+
+# Method is expected to be called with 'more' set to true, but wasn't
+error ExpectedMore ()
+"#;
+
+/// The method calls of the interface.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "parameters")]
+pub enum Method<'a> {
+    /// Get information about the service and the interfaces it implements.
+    #[serde(
+        rename = "org.varlink.service.GetInfo",
+        deserialize_with = "crate::connection::empty_parameters"
+    )]
+    GetInfo,
+    /// Get the IDL description of an interface.
+    #[serde(rename = "org.varlink.service.GetInterfaceDescription")]
+    GetInterfaceDescription {
+        /// The name of the interface.
+        interface: &'a str,
+    },
+}
+
+/// Information about a service.
+///
+/// This serializes to the reply of the `GetInfo` method.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Info<'a> {
+    /// The vendor of the service.
+    pub vendor: &'a str,
+    /// The name of the service.
+    pub product: &'a str,
+    /// The version of the service.
+    pub version: &'a str,
+    /// The URL of the service.
+    pub url: &'a str,
+    /// The IDL descriptions of the interfaces implemented by the service.
+    ///
+    /// The `org.varlink.service` interface is always implied and must not be included.
+    pub descriptions: &'a [&'a str],
+}
+
+impl<'a> Info<'a> {
+    /// The names of the interfaces implemented by the service.
+    ///
+    /// This includes `org.varlink.service`.
+    pub fn interfaces(&self) -> impl Iterator<Item = &'a str> {
+        let descriptions = self.descriptions;
+
+        core::iter::once(INTERFACE_NAME)
+            .chain(descriptions.iter().filter_map(|d| interface_name(d)))
+    }
+
+    /// The IDL description of the interface named `interface`, if the service implements it.
+    pub fn description(&self, interface: &str) -> Option<&'a str> {
+        if interface == INTERFACE_NAME {
+            return Some(DESCRIPTION);
+        }
+
+        self.descriptions
+            .iter()
+            .find(|d| interface_name(d) == Some(interface))
+            .copied()
+    }
+}
+
+impl Serialize for Info<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut info = serializer.serialize_struct("Info", 5)?;
+        info.serialize_field("vendor", self.vendor)?;
+        info.serialize_field("product", self.product)?;
+        info.serialize_field("version", self.version)?;
+        info.serialize_field("url", self.url)?;
+        info.serialize_field("interfaces", &Interfaces(self))?;
+        info.end()
+    }
+}
+
+/// The reply of the `GetInterfaceDescription` method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InterfaceDescription<'a> {
+    /// The IDL description of the interface.
+    pub description: &'a str,
+}
+
+/// The errors of the interface.
+///
+/// These are the standard errors that any Varlink service can reply with. [`Server`] sends them
+/// automatically when appropriate and [`Connection::receive_reply`] returns them as
+/// [`crate::Error::VarlinkService`], even if the `ReplyError` type doesn't include them.
+///
+/// [`Server`]: crate::Server
+/// [`Connection::receive_reply`]: crate::Connection::receive_reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "error", content = "parameters")]
+pub enum Error {
+    /// The requested interface was not found.
+    #[serde(rename = "org.varlink.service.InterfaceNotFound")]
+    InterfaceNotFound {
+        /// The name of the interface.
+        interface: Name,
+    },
+    /// The requested method was not found.
+    #[serde(rename = "org.varlink.service.MethodNotFound")]
+    MethodNotFound {
+        /// The fully-qualified name of the method.
+        method: Name,
+    },
+    /// The interface defines the requested method, but the service does not implement it.
+    #[serde(rename = "org.varlink.service.MethodNotImplemented")]
+    MethodNotImplemented {
+        /// The fully-qualified name of the method.
+        method: Name,
+    },
+    /// One of the passed parameters is invalid.
+    #[serde(rename = "org.varlink.service.InvalidParameter")]
+    InvalidParameter {
+        /// The name of the parameter.
+        parameter: Name,
+    },
+    /// The client is denied access.
+    #[serde(
+        rename = "org.varlink.service.PermissionDenied",
+        deserialize_with = "crate::connection::empty_parameters"
+    )]
+    PermissionDenied,
+    /// The method is expected to be called with the `more` flag set, but wasn't.
+    #[serde(
+        rename = "org.varlink.service.ExpectedMore",
+        deserialize_with = "crate::connection::empty_parameters"
+    )]
+    ExpectedMore,
+}
+
+impl core::error::Error for Error {}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InterfaceNotFound { interface } => {
+                write!(f, "Interface `{interface}` not found")
+            }
+            Error::MethodNotFound { method } => write!(f, "Method `{method}` not found"),
+            Error::MethodNotImplemented { method } => {
+                write!(f, "Method `{method}` not implemented")
+            }
+            Error::InvalidParameter { parameter } => write!(f, "Invalid parameter `{parameter}`"),
+            Error::PermissionDenied => write!(f, "Permission denied"),
+            Error::ExpectedMore => write!(f, "Method expected to be called with `more` flag set"),
+        }
+    }
+}
+
+/// A Varlink name, e.g of an interface, a method or a parameter.
+///
+/// This is an owned string. Without the `std` feature, it's stored inline so it doesn't require an
+/// allocator. Since Varlink names are limited to 255 characters, longer strings are truncated.
+#[derive(Clone)]
+pub struct Name {
+    // Keep it on the heap when we can, so it doesn't bloat `Error` and every `Result` containing it.
+    #[cfg(feature = "std")]
+    name: std::string::String,
+    #[cfg(not(feature = "std"))]
+    buffer: [u8; NAME_MAX_LEN],
+    #[cfg(not(feature = "std"))]
+    len: usize,
+}
+
+impl Name {
+    /// Create a new name, truncating `name` to 255 bytes if it's longer.
+    pub fn new(name: &str) -> Self {
+        let mut len = name.len().min(NAME_MAX_LEN);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        #[cfg(feature = "std")]
+        {
+            Self {
+                name: name[..len].into(),
+            }
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            let mut buffer = [0; NAME_MAX_LEN];
+            buffer[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+            Self { buffer, len }
+        }
+    }
+
+    /// The name as a string slice.
+    pub fn as_str(&self) -> &str {
+        #[cfg(feature = "std")]
+        {
+            &self.name
+        }
+
+        #[cfg(not(feature = "std"))]
+        {
+            // Unwrap is safe because we only ever copy whole characters into the buffer.
+            core::str::from_utf8(&self.buffer[..self.len]).unwrap()
+        }
+    }
+}
+
+impl core::ops::Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Name {}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl core::fmt::Debug for Name {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl core::fmt::Display for Name {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl Serialize for Name {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NameVisitor;
+
+        impl Visitor<'_> for NameVisitor {
+            type Value = Name;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_str<E>(self, name: &str) -> Result<Name, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(Name::new(name))
+            }
+        }
+
+        deserializer.deserialize_str(NameVisitor)
+    }
+}
+
+const NAME_MAX_LEN: usize = 255;
+
+// Serializes the interface names of an `Info` as a sequence.
+struct Interfaces<'i, 'a>(&'i Info<'a>);
+
+impl Serialize for Interfaces<'_, '_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(None)?;
+        for interface in self.0.interfaces() {
+            seq.serialize_element(interface)?;
+        }
+        seq.end()
+    }
+}
+
+// If the `method` is declared in an IDL description.
+pub(crate) fn declares_method(description: &str, method: &str) -> bool {
+    description
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("method"))
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .any(|rest| rest.trim_start().split(['(', ' ', '\t']).next() == Some(method))
+}
+
+// The name of the interface declared in an IDL description.
+//
+// This only looks for the `interface` keyword so it doesn't require the `idl` feature.
+fn interface_name(description: &str) -> Option<&str> {
+    description
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .and_then(|line| line.strip_prefix("interface"))
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .and_then(|rest| rest.split_whitespace().next())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    const FTL: &str = "# Interface to jump a spacecraft to another point in space.\n\
+                       interface org.example.ftl\n\n\
+                       method Jump(destination: string) -> ()\n";
+
+    #[test]
+    fn info() {
+        let info = Info {
+            vendor: "Example",
+            product: "FTL",
+            version: "1",
+            url: "https://example.com",
+            descriptions: &[FTL],
+        };
+        assert_eq!(
+            serde_json::to_string(&info).unwrap(),
+            r#"{"vendor":"Example","product":"FTL","version":"1","url":"https://example.com","interfaces":["org.varlink.service","org.example.ftl"]}"#
+        );
+        assert_eq!(info.description("org.example.ftl"), Some(FTL));
+        assert_eq!(info.description(INTERFACE_NAME), Some(DESCRIPTION));
+        assert_eq!(info.description("org.example.foo"), None);
+    }
+
+    #[test]
+    fn method() {
+        for json in [
+            r#"{"method":"org.varlink.service.GetInfo"}"#,
+            r#"{"method":"org.varlink.service.GetInfo","parameters":null}"#,
+            r#"{"method":"org.varlink.service.GetInfo","parameters":{}}"#,
+        ] {
+            assert_eq!(
+                serde_json::from_str::<Method<'_>>(json).unwrap(),
+                Method::GetInfo
+            );
+        }
+        assert_eq!(
+            serde_json::to_string(&Method::GetInfo).unwrap(),
+            r#"{"method":"org.varlink.service.GetInfo"}"#
+        );
+
+        let json = r#"{"method":"org.varlink.service.GetInterfaceDescription","parameters":{"interface":"org.example.ftl"}}"#;
+        assert_eq!(
+            serde_json::from_str::<Method<'_>>(json).unwrap(),
+            Method::GetInterfaceDescription {
+                interface: "org.example.ftl"
+            }
+        );
+    }
+
+    #[test]
+    fn error() {
+        let error = Error::MethodNotFound {
+            method: Name::new("org.example.ftl.Land"),
+        };
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            json,
+            r#"{"error":"org.varlink.service.MethodNotFound","parameters":{"method":"org.example.ftl.Land"}}"#
+        );
+        assert_eq!(serde_json::from_str::<Error>(&json).unwrap(), error);
+
+        for json in [
+            r#"{"error":"org.varlink.service.ExpectedMore"}"#,
+            r#"{"error":"org.varlink.service.ExpectedMore","parameters":{}}"#,
+        ] {
+            assert_eq!(
+                serde_json::from_str::<Error>(json).unwrap(),
+                Error::ExpectedMore
+            );
+        }
+        let json = r#"{"error":"org.varlink.service.PermissionDenied","parameters":{}}"#;
+        assert_eq!(
+            serde_json::from_str::<Error>(json).unwrap(),
+            Error::PermissionDenied
+        );
+
+        // Keep the errors small, so results containing them don't trigger
+        // `clippy::result_large_err`.
+        assert!(core::mem::size_of::<crate::Error<&str>>() <= 64);
+
+        let long = "a".repeat(300);
+        assert_eq!(Name::new(&long).len(), 255);
+    }
+}